@@ -1,7 +1,19 @@
 #version 330 core
 
+in vec4 gs_color;
+in vec2 gs_local;
+flat in vec2 gs_half_size;
+flat in float gs_radius;
+
 out vec4 fs_out;
 
 void main() {
-    fs_out = vec4(1, 1, 1, 1);
+    // Signed distance to a rounded rect, which covers plain rects (radius 0)
+    // and circles (radius of half the size) too.
+    vec2 q = abs(gs_local) - gs_half_size + gs_radius;
+    float distance = length(max(q, 0)) + min(max(q.x, q.y), 0) - gs_radius;
+    // Fade over about one pixel around the edge.
+    float pixel = max(fwidth(distance), 1e-4);
+    float coverage = clamp(0.5 - distance / pixel, 0, 1);
+    fs_out = vec4(gs_color.rgb, gs_color.a * coverage);
 }
@@ -5,18 +5,36 @@ layout (triangle_strip, max_vertices=4) out;
 
 in VS_OUT {
     vec2 dimension;
+    vec2 size;
+    vec4 color;
+    float radius;
 } gs_in [];
 
-void main() {
-    vec4 x_offset = vec4(gs_in[0].dimension.x, 0, 0, 0);
-    vec4 y_offset = vec4(0, gs_in[0].dimension.y, 0, 0);
+out vec4 gs_color;
+// Position relative to the shape's centre, in board units.
+out vec2 gs_local;
+flat out vec2 gs_half_size;
+flat out float gs_radius;
 
-    gl_Position = gl_in[0].gl_Position;
-    EmitVertex();
-    gl_Position = gl_in[0].gl_Position + y_offset;
-    EmitVertex();
-    gl_Position = gl_in[0].gl_Position + x_offset;
-    EmitVertex();
-    gl_Position = gl_in[0].gl_Position + x_offset + y_offset;
+uniform vec2 projection;
+// One window pixel, in board units.
+uniform float pixel;
+
+void emit(vec2 corner) {
+    // The quad reaches a pixel past the shape on every side, so the fragment
+    // shader's fade can run outside the edge as well as inside it.
+    vec2 margin = (2 * corner - 1) * pixel;
+    gs_color = gs_in[0].color;
+    gs_local = (corner - 0.5) * gs_in[0].size + margin;
+    gs_half_size = gs_in[0].size / 2;
+    gs_radius = gs_in[0].radius;
+    gl_Position = gl_in[0].gl_Position + vec4(corner * gs_in[0].dimension + 2 * margin / projection, 0, 0);
     EmitVertex();
 }
+
+void main() {
+    emit(vec2(0, 0));
+    emit(vec2(0, 1));
+    emit(vec2(1, 0));
+    emit(vec2(1, 1));
+}
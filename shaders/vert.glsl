@@ -2,15 +2,23 @@
 
 layout(location=0) in vec2 position;
 layout(location=1) in vec2 dimension;
+layout(location=2) in vec4 color;
+layout(location=3) in float radius;
 
 uniform vec2 projection;
 uniform vec2 offset;
 
 out VS_OUT {
     vec2 dimension;
+    vec2 size;
+    vec4 color;
+    float radius;
 } vs_out;
 
 void main() {
     vs_out.dimension = 2 * dimension / projection;
+    vs_out.size = dimension;
+    vs_out.color = color;
+    vs_out.radius = radius;
     gl_Position = vec4((2 * (position + offset) - projection) / projection, 0, 1);
 }
@@ -0,0 +1,312 @@
+use game;
+use game::{Board, Controller, PaddleController, Side};
+
+const MAX_PREDICTED_BOUNCES: u32 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None
+        }
+    }
+
+    pub fn controller(&self) -> Box<dyn PaddleController> {
+        match *self {
+            Difficulty::Easy => Box::new(ChaseAi {
+                pid: Pid::new(*self),
+                own_half_only: true
+            }),
+            Difficulty::Medium => Box::new(ChaseAi {
+                pid: Pid::new(*self),
+                own_half_only: false
+            }),
+            Difficulty::Hard => Box::new(InterceptAi {
+                pid: Pid::new(*self)
+            })
+        }
+    }
+}
+
+/// A nameable description of a `game::Controller`, so it can be picked on the
+/// command line and stored in replays.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ControllerKind {
+    Human,
+    Ai(Difficulty),
+    Idle
+}
+
+impl ControllerKind {
+    pub fn name(&self) -> String {
+        match *self {
+            ControllerKind::Human => "human".to_string(),
+            ControllerKind::Ai(difficulty) => format!("ai:{}", difficulty.name()),
+            ControllerKind::Idle => "idle".to_string()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ControllerKind> {
+        match name {
+            "human" => Some(ControllerKind::Human),
+            "idle" => Some(ControllerKind::Idle),
+            _ if name.starts_with("ai:") => Difficulty::from_name(&name[3..]).map(ControllerKind::Ai),
+            _ => None
+        }
+    }
+
+    pub fn controller(&self) -> Controller {
+        match *self {
+            ControllerKind::Human => Controller::Human,
+            ControllerKind::Ai(difficulty) => Controller::Ai(difficulty.controller()),
+            ControllerKind::Idle => Controller::Idle
+        }
+    }
+}
+
+/// PID loop steering a paddle's centre towards a target height. The
+/// difficulty scales both the gains and the acceleration limit.
+#[derive(Clone)]
+struct Pid {
+    difficulty: Difficulty,
+    last_offset: f32,
+    accum_offset: f32
+}
+
+impl Pid {
+    fn new(difficulty: Difficulty) -> Self {
+        Pid {
+            difficulty,
+            last_offset: 0.,
+            accum_offset: 0.
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_offset = 0.;
+        self.accum_offset = 0.;
+    }
+
+    fn steer(&mut self, board: &Board, side: Side, target: f32, dt: f32) -> f32 {
+        let paddle = board.paddle(side);
+        let config = &board.config.ai;
+        let aggression = match self.difficulty {
+            Difficulty::Easy => config.easy_slowdown,
+            Difficulty::Medium => 1.,
+            Difficulty::Hard => config.hard_speedup
+        };
+        let target_offset = target - (paddle.bound.y + paddle.bound.height / 2.);
+        if self.accum_offset.signum() != target_offset.signum() {
+            self.accum_offset = 0.;
+        } else {
+            self.accum_offset += target_offset;
+        }
+        let p = target_offset;
+        let i = self.accum_offset;
+        let d = (p - self.last_offset) / dt;
+        self.last_offset = target_offset;
+        let max_accel = config.max_accel * aggression;
+        let ddy_diff = aggression * (config.p_factor * p + config.i_factor * i + config.d_factor * d) - paddle.ddy;
+        if ddy_diff.abs() > max_accel {
+            paddle.ddy + ddy_diff.signum() * max_accel
+        } else {
+            paddle.ddy + ddy_diff
+        }
+    }
+}
+
+fn ball_approaches(board: &Board, side: Side) -> bool {
+    match side {
+        Side::Lhs => board.ball.dx < 0.,
+        Side::Rhs => board.ball.dx > 0.
+    }
+}
+
+/// Follows the ball's current height while it approaches, and drifts back to
+/// the middle otherwise. With `own_half_only` it ignores the ball until it
+/// crosses the net.
+#[derive(Clone)]
+pub struct ChaseAi {
+    pid: Pid,
+    own_half_only: bool
+}
+
+impl PaddleController for ChaseAi {
+    fn accel(&mut self, board: &Board, side: Side, dt: f32) -> f32 {
+        let ball_x = board.ball.bound.x + board.ball.bound.width / 2.;
+        let in_own_half = match side {
+            Side::Lhs => ball_x < board.width / 2.,
+            Side::Rhs => ball_x > board.width / 2.
+        };
+        let target = if ball_approaches(board, side) && (in_own_half || !self.own_half_only) {
+            board.ball.bound.y + board.ball.bound.height / 2.
+        } else {
+            board.height / 2.
+        };
+        self.pid.steer(board, side, target, dt)
+    }
+
+    fn reset(&mut self) {
+        self.pid.reset();
+    }
+
+    fn box_clone(&self) -> Box<dyn PaddleController> {
+        Box::new(self.clone())
+    }
+}
+
+/// Moves to where the ball will cross the paddle's face, found by ray-casting
+/// its path off the top and bottom walls.
+#[derive(Clone)]
+pub struct InterceptAi {
+    pid: Pid
+}
+
+/// Predicts the height at which the ball's centre reaches `x`.
+pub fn predict_intercept(board: &Board, x: f32) -> f32 {
+    let radius = board.ball.bound.width / 2.;
+    let (mut sx, mut sy) = (board.ball.bound.x + radius, board.ball.bound.y + radius);
+    let (dx, mut dy) = (board.ball.dx, board.ball.dy);
+    let walls = [
+        (0., radius, board.width, 0.),
+        (0., board.height - radius, board.width, 0.)
+    ];
+    for _ in 0..MAX_PREDICTED_BOUNCES {
+        let t = (x - sx) / dx;
+        if t.is_nan() || t <= 0. {
+            break;
+        }
+        let (sdx, sdy) = (dx * t, dy * t);
+        let hit = walls.iter()
+            .filter(|&&(_, ty, _, _)| (ty - sy) * dy > 0.)
+            .map(|&(tx, ty, tdx, tdy)| game::collides(sx, sy, sdx, sdy, tx, ty, tdx, tdy))
+            .find(|&(cs, ct)| 0. < cs && cs <= 1. && (0. ..=1.).contains(&ct));
+        match hit {
+            Some((cs, _)) => {
+                sx += sdx * cs;
+                sy += sdy * cs;
+                dy = -dy;
+            },
+            None => return sy + sdy
+        }
+    }
+    sy
+}
+
+impl PaddleController for InterceptAi {
+    fn accel(&mut self, board: &Board, side: Side, dt: f32) -> f32 {
+        let paddle = board.paddle(side);
+        let radius = board.ball.bound.width / 2.;
+        let target = if ball_approaches(board, side) {
+            let face_x = match side {
+                Side::Lhs => paddle.bound.x + paddle.bound.width + radius,
+                Side::Rhs => paddle.bound.x - radius
+            };
+            predict_intercept(board, face_x)
+        } else {
+            board.height / 2.
+        };
+        self.pid.steer(board, side, target, dt)
+    }
+
+    fn reset(&mut self) {
+        self.pid.reset();
+    }
+
+    fn box_clone(&self) -> Box<dyn PaddleController> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use game::{Event, TICK_DT, TICK_RATE};
+
+    /// Sends the ball from `x` at `(dx, dy)` towards the paddle on `side`,
+    /// parks that paddle on the predicted crossing and steps the board until
+    /// the ball reaches it. Returns the prediction, where the ball really hit
+    /// and how many walls it bounced off on the way.
+    fn predict_and_play(side: Side, x: f32, dx: f32, dy: f32) -> (f32, f32, usize) {
+        let mut board = Board::new(GameConfig::default());
+        board.start_game(true);
+        let radius = board.config.ball_radius;
+        board.ball.bound.x = x - radius;
+        board.ball.dx = dx;
+        board.ball.dy = dy;
+        let paddle = board.paddle(side).bound;
+        let face_x = match side {
+            Side::Lhs => paddle.x + paddle.width + radius,
+            Side::Rhs => paddle.x - radius
+        };
+        let predicted = predict_intercept(&board, face_x);
+        let paddle_y = predicted - paddle.height / 2.;
+        match side {
+            Side::Lhs => board.lhs_paddle.bound.y = paddle_y,
+            Side::Rhs => board.rhs_paddle.bound.y = paddle_y
+        }
+        let mut bounces = 0;
+        for _ in 0..5 * TICK_RATE {
+            board.update(TICK_DT);
+            for event in board.drain_events() {
+                match event {
+                    Event::WallHit(_) => bounces += 1,
+                    Event::PaddleHit(hit, impact, _) if hit == side => return (predicted, impact.y, bounces),
+                    other => panic!("unexpected {:?}", other)
+                }
+            }
+        }
+        panic!("the ball never reached the paddle");
+    }
+
+    fn assert_intercept(side: Side, x: f32, dx: f32, dy: f32, walls: usize) {
+        let (predicted, actual, bounces) = predict_and_play(side, x, dx, dy);
+        assert_eq!(bounces, walls, "expected {} wall bounces", walls);
+        assert!((predicted - actual).abs() < 0.01, "predicted {} but the ball hit at {}", predicted, actual);
+    }
+
+    #[test]
+    fn predicts_a_straight_shot() {
+        assert_intercept(Side::Lhs, 300., -300., 60., 0);
+        assert_intercept(Side::Rhs, 300., 300., -60., 0);
+    }
+
+    #[test]
+    fn predicts_a_shot_off_one_wall() {
+        assert_intercept(Side::Lhs, 300., -300., 300., 1);
+        assert_intercept(Side::Rhs, 300., 300., -300., 1);
+    }
+
+    #[test]
+    fn predicts_a_shot_off_both_walls() {
+        assert_intercept(Side::Lhs, 550., -300., 300., 2);
+        assert_intercept(Side::Rhs, 50., 300., -300., 2);
+    }
+
+    #[test]
+    fn a_ball_moving_away_is_not_projected() {
+        let mut board = Board::new(GameConfig::default());
+        board.start_game(true);
+        board.ball.dx = 300.;
+        let centre = board.ball.bound.y + board.ball.bound.height / 2.;
+        assert_eq!(predict_intercept(&board, 20.), centre);
+    }
+}
@@ -0,0 +1,165 @@
+use std::env;
+use std::fmt;
+use std::fs;
+#[cfg(feature = "audio")]
+use std::io::{self, Write};
+use std::path::PathBuf;
+#[cfg(feature = "audio")]
+use std::process;
+
+/// Environment variable with extra asset directories, separated like `PATH`.
+pub const ASSETS_ENV: &str = "PONG_ASSETS";
+
+/// How many names `AssetFile::temporary` tries before giving up.
+#[cfg(feature = "audio")]
+const MAX_TEMPORARY_ATTEMPTS: u32 = 100;
+
+pub const VERT_SHADER: &str = "shaders/vert.glsl";
+pub const GEOM_SHADER: &str = "shaders/geom.glsl";
+pub const FRAG_SHADER: &str = "shaders/frag.glsl";
+pub const BEEP_SOUND: &str = "sounds/beep.wav";
+pub const TICK_SOUND: &str = "sounds/tick.wav";
+pub const ERROR_SOUND: &str = "sounds/error.wav";
+
+/// Every asset compiled into the binary, by its path relative to an asset
+/// directory.
+const EMBEDDED: &[(&str, &[u8])] = &[
+    (VERT_SHADER, include_bytes!("../shaders/vert.glsl")),
+    (GEOM_SHADER, include_bytes!("../shaders/geom.glsl")),
+    (FRAG_SHADER, include_bytes!("../shaders/frag.glsl")),
+    (BEEP_SOUND, include_bytes!("../sounds/beep.wav")),
+    (TICK_SOUND, include_bytes!("../sounds/tick.wav")),
+    (ERROR_SOUND, include_bytes!("../sounds/error.wav"))
+];
+
+#[derive(Debug)]
+pub struct AssetError {
+    pub name: String,
+    /// Every file that was looked for, in order.
+    pub tried: Vec<PathBuf>,
+    pub message: String
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)?;
+        if !self.tried.is_empty() {
+            let tried: Vec<_> = self.tried.iter().map(|path| path.display().to_string()).collect();
+            write!(f, " (looked in {})", tried.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds assets in the override directories first, falling back to the
+/// copies built into the binary, so any single file can be replaced.
+#[derive(Clone, Debug)]
+pub struct Assets {
+    dirs: Vec<PathBuf>
+}
+
+impl Assets {
+    /// Searches `dir`, if given, then the directories in `PONG_ASSETS`.
+    pub fn new(dir: Option<&str>) -> Self {
+        let mut dirs: Vec<PathBuf> = dir.into_iter().map(PathBuf::from).collect();
+        if let Some(paths) = env::var_os(ASSETS_ENV) {
+            dirs.extend(env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()));
+        }
+        Assets { dirs }
+    }
+
+    /// Whether there's any directory to look for override files in.
+    pub fn has_dirs(&self) -> bool {
+        !self.dirs.is_empty()
+    }
+
+    /// The override file for `name`, if there is one.
+    pub fn locate(&self, name: &str) -> Option<PathBuf> {
+        self.dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file())
+    }
+
+    pub fn load(&self, name: &str) -> Result<Vec<u8>, AssetError> {
+        if let Some(path) = self.locate(name) {
+            return fs::read(&path).map_err(|e| AssetError {
+                name: name.to_string(),
+                tried: vec![path],
+                message: e.to_string()
+            });
+        }
+        EMBEDDED.iter()
+            .find(|&&(embedded, _)| embedded == name)
+            .map(|&(_, data)| data.to_vec())
+            .ok_or_else(|| AssetError {
+                name: name.to_string(),
+                tried: self.dirs.iter().map(|dir| dir.join(name)).collect(),
+                message: "not found, and there's no built-in copy".to_string()
+            })
+    }
+
+    pub fn load_text(&self, name: &str) -> Result<String, AssetError> {
+        let data = self.load(name)?;
+        String::from_utf8(data).map_err(|_| AssetError {
+            name: name.to_string(),
+            tried: self.locate(name).into_iter().collect(),
+            message: "isn't valid UTF-8".to_string()
+        })
+    }
+
+    /// A file holding `name`, for libraries that only open paths. Built-in
+    /// assets are written to a temporary file for as long as the returned
+    /// value lives.
+    #[cfg(feature = "audio")]
+    pub fn file(&self, name: &str) -> Result<AssetFile, AssetError> {
+        if let Some(path) = self.locate(name) {
+            return Ok(AssetFile { path, temporary: false });
+        }
+        let data = self.load(name)?;
+        AssetFile::temporary(name, &data).map_err(|e| AssetError {
+            name: name.to_string(),
+            tried: Vec::new(),
+            message: format!("can't write a temporary copy: {}", e)
+        })
+    }
+}
+
+#[cfg(feature = "audio")]
+pub struct AssetFile {
+    pub path: PathBuf,
+    temporary: bool
+}
+
+#[cfg(feature = "audio")]
+impl AssetFile {
+    /// Writes `data` to a new temporary file named after `name`, which is
+    /// removed when the returned value is dropped. Never opens a file that
+    /// already exists, so nothing planted in the temporary directory gets
+    /// written through.
+    pub fn temporary(name: &str, data: &[u8]) -> io::Result<AssetFile> {
+        let mut attempt = 0;
+        loop {
+            let file_name = format!("pong-{}-{}-{}", process::id(), attempt, name.replace('/', "-"));
+            let path = env::temp_dir().join(file_name);
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let asset_file = AssetFile { path, temporary: true };
+                    // Dropping `asset_file` on error removes the partial copy.
+                    file.write_all(data)?;
+                    return Ok(asset_file);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < MAX_TEMPORARY_ATTEMPTS => {
+                    attempt += 1;
+                },
+                Err(e) => return Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Drop for AssetFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
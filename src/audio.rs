@@ -0,0 +1,247 @@
+#[cfg(feature = "audio")]
+extern crate ears;
+
+use assets;
+#[cfg(feature = "audio")]
+use config::AudioConfig;
+use config::GameConfig;
+use game;
+#[cfg(feature = "audio")]
+use synth;
+#[cfg(feature = "audio")]
+use ears::{AudioController, Sound, SoundData};
+#[cfg(feature = "audio")]
+use std::cell::RefCell;
+#[cfg(feature = "audio")]
+use std::path::Path;
+#[cfg(feature = "audio")]
+use std::rc::Rc;
+
+// The go beep is an octave above the countdown beeps.
+#[cfg(feature = "audio")]
+const GO_PITCH: f32 = 2.;
+
+/// Something that plays sounds for board events.
+pub trait AudioBackend {
+    fn handle_event(&mut self, board: &game::Board, event: &game::Event);
+
+    /// Beeps for a serve countdown with `seconds_left`, or 0 for the serve.
+    fn countdown(&mut self, board: &game::Board, seconds_left: u32);
+}
+
+/// Picks the OpenAL backend, or the silent one when `mute` is set, OpenAL
+/// can't start or the game was built without the `audio` feature. A failed
+/// start is only a warning; the game plays on without sound.
+pub fn open(assets: &assets::Assets, config: &GameConfig, mute: bool) -> Box<dyn AudioBackend> {
+    if mute {
+        return Box::new(NullAudio);
+    }
+    start(assets, config).unwrap_or_else(|e| {
+        println!("Warning: playing without sound, {}", e);
+        Box::new(NullAudio)
+    })
+}
+
+#[cfg(feature = "audio")]
+fn start(assets: &assets::Assets, config: &GameConfig) -> Result<Box<dyn AudioBackend>, String> {
+    Ok(Box::new(OpenAlAudio::new(assets, config)?))
+}
+
+#[cfg(not(feature = "audio"))]
+fn start(_assets: &assets::Assets, _config: &GameConfig) -> Result<Box<dyn AudioBackend>, String> {
+    Err("this build has no audio support".to_string())
+}
+
+/// Gain for sound effects after the master volume.
+#[cfg(feature = "audio")]
+pub fn sfx_gain(config: &AudioConfig) -> f32 {
+    (config.master_volume * config.sfx_volume).clamp(0., 1.)
+}
+
+/// Stereo position for a point `x` across the board, from -1 (left) to 1.
+#[cfg(feature = "audio")]
+pub fn pan(board: &game::Board, x: f32) -> f32 {
+    (board.config.audio.stereo_width * (2. * x / board.width - 1.)).clamp(-1., 1.)
+}
+
+/// Pitch for a hit that leaves the ball at `speed`: 1 at the serve speed,
+/// rising to `1 + max_pitch_rise` at the top speed.
+#[cfg(feature = "audio")]
+pub fn hit_pitch(board: &game::Board, speed: f32) -> f32 {
+    let config = &board.config;
+    let range = (config.ball_max_speed - config.ball_start_speed).max(1.);
+    let fraction = ((speed - config.ball_start_speed) / range).clamp(0., 1.);
+    1. + config.audio.max_pitch_rise * fraction
+}
+
+/// Pitch for a paddle hit at `ct` along the paddle, spreading hits across
+/// `synth.paddle_pitch_spread` octaves with the middle unchanged. The classic
+/// pack's recorded beep always plays at its own pitch.
+#[cfg(any(feature = "audio", test))]
+pub fn paddle_pitch(board: &game::Board, ct: f32) -> f32 {
+    if board.config.audio.sound_pack != "synth" {
+        return 1.;
+    }
+    2f32.powf(board.config.synth.paddle_pitch_spread * (ct.clamp(0., 1.) - 0.5))
+}
+
+/// Several sources sharing one decoded sound, so a sound can overlap itself
+/// instead of restarting.
+#[cfg(feature = "audio")]
+struct VoicePool {
+    voices: Vec<Sound>,
+    // Voice to steal when they're all busy; they're started in this order.
+    next: usize
+}
+
+#[cfg(feature = "audio")]
+impl VoicePool {
+    /// Decodes `path`, which can be removed as soon as this returns.
+    fn load(path: &Path, count: u32) -> Result<Self, String> {
+        let data = SoundData::new(&path.to_string_lossy()).ok_or("can't be decoded")?;
+        let data = Rc::new(RefCell::new(data));
+        let mut voices = Vec::new();
+        for _ in 0..count.max(1) {
+            let mut voice = Sound::new_with_data(data.clone()).ok_or("can't be played")?;
+            // Positions are relative to the listener, so they only pan.
+            voice.set_relative(true);
+            voices.push(voice);
+        }
+        Ok(VoicePool { voices, next: 0 })
+    }
+
+    fn from_asset(assets: &assets::Assets, name: &str, count: u32) -> Result<Self, String> {
+        let file = assets.file(name).map_err(|e| e.to_string())?;
+        VoicePool::load(&file.path, count).map_err(|e| {
+            let tried: Vec<_> = assets.locate(name).into_iter().map(|path| path.display().to_string()).collect();
+            match tried.first() {
+                Some(path) => format!("{}: {} ({})", name, e, path),
+                None => format!("{}: {}", name, e)
+            }
+        })
+    }
+
+    /// Renders `blip` and loads it through a temporary WAV file, since ears
+    /// only reads sounds from disk.
+    fn from_blip(name: &str, blip: &synth::Blip, count: u32) -> Result<Self, String> {
+        let wav = synth::write_wav(&blip.render(synth::SAMPLE_RATE), synth::SAMPLE_RATE);
+        let file = assets::AssetFile::temporary(&format!("{}.wav", name), &wav)
+            .map_err(|e| format!("{}: can't write a temporary file: {}", name, e))?;
+        VoicePool::load(&file.path, count).map_err(|e| format!("synthesized {}: {}", name, e))
+    }
+
+    fn play(&mut self, gain: f32, pan: f32, pitch: f32) {
+        let index = match self.voices.iter().position(|voice| !voice.is_playing()) {
+            Some(index) => index,
+            None => self.next
+        };
+        self.next = (index + 1) % self.voices.len();
+        let voice = &mut self.voices[index];
+        voice.stop();
+        voice.set_volume(gain);
+        voice.set_pitch(pitch);
+        // On the unit circle in front of the listener, so the distance and
+        // with it the loudness stay the same wherever it's panned.
+        voice.set_position([pan, 0., -(1. - pan * pan).sqrt()]);
+        voice.play();
+    }
+}
+
+#[cfg(feature = "audio")]
+pub struct OpenAlAudio {
+    paddle: VoicePool,
+    wall: VoicePool,
+    goal: VoicePool,
+    countdown: VoicePool
+}
+
+#[cfg(feature = "audio")]
+impl OpenAlAudio {
+    pub fn new(assets: &assets::Assets, config: &GameConfig) -> Result<Self, String> {
+        if !ears::init() {
+            return Err("no OpenAL device could be opened".to_string());
+        }
+        let voices = config.audio.voices;
+        let synth = &config.synth;
+        let countdown = VoicePool::from_blip("countdown", &synth.countdown.blip(), voices);
+        let (paddle, wall, goal) = if config.audio.sound_pack == "synth" {
+            (VoicePool::from_blip("paddle", &synth.paddle.blip(), voices),
+             VoicePool::from_blip("wall", &synth.wall.blip(), voices),
+             VoicePool::from_blip("goal", &synth.goal.blip(), voices))
+        } else {
+            (VoicePool::from_asset(assets, assets::BEEP_SOUND, voices),
+             VoicePool::from_asset(assets, assets::TICK_SOUND, voices),
+             VoicePool::from_asset(assets, assets::ERROR_SOUND, voices))
+        };
+        let load = |pool: Result<VoicePool, String>| pool.map_err(|e| format!("can't load sound {}", e));
+        Ok(OpenAlAudio {
+            paddle: load(paddle)?,
+            wall: load(wall)?,
+            goal: load(goal)?,
+            countdown: load(countdown)?
+        })
+    }
+}
+
+#[cfg(feature = "audio")]
+impl AudioBackend for OpenAlAudio {
+    fn handle_event(&mut self, board: &game::Board, event: &game::Event) {
+        let gain = sfx_gain(&board.config.audio);
+        match *event {
+            game::Event::PaddleHit(_, impact, ct) => {
+                let pitch = hit_pitch(board, impact.speed()) * paddle_pitch(board, ct);
+                self.paddle.play(gain, pan(board, impact.x), pitch);
+            },
+            game::Event::WallHit(impact) => {
+                self.wall.play(gain, pan(board, impact.x), hit_pitch(board, impact.speed()));
+            },
+            game::Event::Goal(_, impact) => self.goal.play(gain, pan(board, impact.x), 1.),
+            game::Event::PowerUp(_, _, impact) => self.countdown.play(gain, pan(board, impact.x), GO_PITCH)
+        }
+    }
+
+    fn countdown(&mut self, board: &game::Board, seconds_left: u32) {
+        let pitch = if seconds_left == 0 { GO_PITCH } else { 1. };
+        self.countdown.play(sfx_gain(&board.config.audio), 0., pitch);
+    }
+}
+
+/// Plays nothing, for `--mute` and machines without a sound device.
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn handle_event(&mut self, _board: &game::Board, _event: &game::Event) {}
+
+    fn countdown(&mut self, _board: &game::Board, _seconds_left: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(sound_pack: &str) -> game::Board {
+        let mut config = GameConfig::default();
+        config.audio.sound_pack = sound_pack.to_string();
+        game::Board::new(config)
+    }
+
+    #[test]
+    fn synth_paddle_pitch_rises_along_the_paddle() {
+        let board = board("synth");
+        let spread = board.config.synth.paddle_pitch_spread;
+        assert_eq!(paddle_pitch(&board, 0.5), 1.);
+        assert!(paddle_pitch(&board, 0.) < paddle_pitch(&board, 0.25));
+        assert!(paddle_pitch(&board, 0.75) < paddle_pitch(&board, 1.));
+        let octaves = (paddle_pitch(&board, 1.) / paddle_pitch(&board, 0.)).log2();
+        assert!((octaves - spread).abs() < 1e-4);
+        assert_eq!(paddle_pitch(&board, 2.), paddle_pitch(&board, 1.));
+    }
+
+    #[test]
+    fn classic_paddle_pitch_is_flat() {
+        let board = board("classic");
+        for &ct in &[0., 0.25, 0.5, 1.] {
+            assert_eq!(paddle_pitch(&board, ct), 1.);
+        }
+    }
+}
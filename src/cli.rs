@@ -0,0 +1,402 @@
+use ai::Difficulty;
+
+pub const USAGE: &str = "\
+Usage: pong [options]
+
+Match setup:
+  --mode <mode>          vs-ai (default), two-player, ai-vs-ai or replay
+  --difficulty <level>   AI difficulty: easy, medium (default) or hard
+  --win-score <points>   Points needed to win, overriding the config file
+  --serve <side>         Who serves first: left (default), right or random
+  --seed <number>        Seed for serve side and angle, random by default
+  --config <file>        Load match tuning from a TOML file
+  --assets <dir>         Use shaders and sounds from this directory over the
+                         built-in ones; PONG_ASSETS adds more directories
+
+Display:
+  --scale <factor>       Starting window scale, e.g. 2 or 1.5; the board then
+                         scales to fit the window
+  --fullscreen           Start fullscreen; F11 or Alt+Enter toggles it
+  --integer-scale        Scale by whole pixels only, for crisp edges
+  --show-fps             Show the frame rate in a corner
+  --screenshot <file>    Render the opening screen to a PNG without a window,
+                         or the final one when combined with --headless
+  --tui                  Play in the terminal instead of a window
+  --headless             Run the match without a window and print the result;
+                         needs a mode without human players
+  --dev                  Reload shaders in the asset directories (or the
+                         current directory if none are given) when they
+                         change, showing compile errors in the window
+
+Sound:
+  --mute                 Play without sound
+
+Controls:
+  --bindings <file>      Load key bindings from a file
+  --mouse                Steer your paddle with the mouse, the left one
+                         outside netplay
+
+Recording:
+  --record <file>        Save the match inputs to a replay file
+  --replay <file>        Play back a replay file (implies --mode replay), with
+                         the --config file it was recorded with
+
+Netplay:
+  --host <port>          Host a network match on the given UDP port
+  --join <address>       Join a network match, e.g. 192.168.1.2:7000
+                         Both players need the same --config and --win-score
+
+  -h, --help             Show this help
+";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    VsAi,
+    TwoPlayer,
+    AiVsAi,
+    Replay
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mode::VsAi => "vs-ai",
+            Mode::TwoPlayer => "two-player",
+            Mode::AiVsAi => "ai-vs-ai",
+            Mode::Replay => "replay"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "vs-ai" => Some(Mode::VsAi),
+            "two-player" => Some(Mode::TwoPlayer),
+            "ai-vs-ai" => Some(Mode::AiVsAi),
+            "replay" => Some(Mode::Replay),
+            _ => None
+        }
+    }
+
+    pub fn has_human(&self) -> bool {
+        match *self {
+            Mode::VsAi | Mode::TwoPlayer => true,
+            Mode::AiVsAi | Mode::Replay => false
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Serve {
+    Left,
+    Right,
+    Random
+}
+
+impl Serve {
+    pub fn from_name(name: &str) -> Option<Serve> {
+        match name {
+            "left" => Some(Serve::Left),
+            "right" => Some(Serve::Right),
+            "random" => Some(Serve::Random),
+            _ => None
+        }
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    pub difficulty: Difficulty,
+    pub win_score: Option<u32>,
+    pub serve: Serve,
+    pub seed: Option<u64>,
+    pub config_path: Option<String>,
+    pub assets_dir: Option<String>,
+    pub scale: f32,
+    pub headless: bool,
+    pub tui: bool,
+    pub show_fps: bool,
+    pub fullscreen: bool,
+    pub integer_scale: bool,
+    pub dev: bool,
+    pub mute: bool,
+    pub screenshot_path: Option<String>,
+    pub record_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub bindings_path: Option<String>,
+    pub mouse: bool,
+    pub host_port: Option<u16>,
+    pub join_addr: Option<String>
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mode: Mode::VsAi,
+            difficulty: Difficulty::Medium,
+            win_score: None,
+            serve: Serve::Left,
+            seed: None,
+            config_path: None,
+            assets_dir: None,
+            scale: 1.,
+            headless: false,
+            tui: false,
+            show_fps: false,
+            fullscreen: false,
+            integer_scale: false,
+            dev: false,
+            mute: false,
+            screenshot_path: None,
+            record_path: None,
+            replay_path: None,
+            bindings_path: None,
+            mouse: false,
+            host_port: None,
+            join_addr: None
+        }
+    }
+}
+
+pub enum Command {
+    Help,
+    Run(Box<Options>)
+}
+
+/// Parses the arguments after the program name. Options take their value
+/// either as the next argument or after an `=`, as in `--mode=ai-vs-ai`.
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut mode = None;
+    let mut serve = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+            _ => (arg.clone(), None)
+        };
+        let takes_value = match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--headless" | "--tui" | "--show-fps" | "--dev" | "--mute" |
+            "--mouse" | "--fullscreen" | "--integer-scale" => false,
+            "--mode" | "--difficulty" | "--win-score" | "--serve" | "--seed" | "--config" | "--assets" |
+            "--scale" | "--screenshot" | "--record" | "--replay" | "--bindings" | "--host" | "--join" => true,
+            _ => return Err(format!("unknown argument `{}`", arg))
+        };
+        if !takes_value {
+            if inline_value.is_some() {
+                return Err(format!("{} doesn't take a value", flag));
+            }
+            match flag.as_str() {
+                "--headless" => options.headless = true,
+                "--tui" => options.tui = true,
+                "--show-fps" => options.show_fps = true,
+                "--dev" => options.dev = true,
+                "--mute" => options.mute = true,
+                "--mouse" => options.mouse = true,
+                "--fullscreen" => options.fullscreen = true,
+                "--integer-scale" => options.integer_scale = true,
+                _ => unreachable!("switch {} has no handler", flag)
+            }
+            continue;
+        }
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("{} needs a value", flag))
+        };
+        let invalid = |expected: &str| format!("invalid value `{}` for {}, expected {}", value, flag, expected);
+        match flag.as_str() {
+            "--mode" => {
+                mode = Some(Mode::from_name(&value)
+                    .ok_or_else(|| invalid("vs-ai, two-player, ai-vs-ai or replay"))?);
+            },
+            "--difficulty" => {
+                options.difficulty = Difficulty::from_name(&value)
+                    .ok_or_else(|| invalid("easy, medium or hard"))?;
+            },
+            "--win-score" => {
+                options.win_score = Some(value.parse().ok()
+                    .filter(|&score| score > 0)
+                    .ok_or_else(|| invalid("a positive whole number"))?);
+            },
+            "--serve" => {
+                serve = Some(Serve::from_name(&value).ok_or_else(|| invalid("left, right or random"))?);
+            },
+            "--seed" => options.seed = Some(value.parse().map_err(|_| invalid("a whole number"))?),
+            "--scale" => {
+                options.scale = value.parse().ok()
+                    .filter(|&scale: &f32| scale.is_finite() && scale > 0.)
+                    .ok_or_else(|| invalid("a positive number"))?;
+            },
+            "--host" => options.host_port = Some(value.parse().map_err(|_| invalid("a port number"))?),
+            "--config" => options.config_path = Some(value),
+            "--assets" => options.assets_dir = Some(value),
+            "--screenshot" => options.screenshot_path = Some(value),
+            "--record" => options.record_path = Some(value),
+            "--replay" => options.replay_path = Some(value),
+            "--bindings" => options.bindings_path = Some(value),
+            "--join" => options.join_addr = Some(value),
+            _ => unreachable!("option {} has no handler", flag)
+        }
+    }
+
+    options.mode = match (mode, options.replay_path.is_some()) {
+        (Some(Mode::Replay), false) => return Err("--mode replay needs a --replay file".to_string()),
+        (Some(mode), true) if mode != Mode::Replay => {
+            return Err(format!("--replay can't be used with --mode {}", mode.name()));
+        },
+        (_, true) => Mode::Replay,
+        (Some(mode), false) => mode,
+        (None, false) => Mode::VsAi
+    };
+    if options.mode == Mode::Replay {
+        if serve.is_some() || options.seed.is_some() || options.win_score.is_some() {
+            return Err("--serve, --seed and --win-score come from the recording when playing a replay".to_string());
+        }
+        if options.record_path.is_some() {
+            return Err("--record can't be used while playing a replay".to_string());
+        }
+    }
+    options.serve = serve.unwrap_or(Serve::Left);
+    if options.headless && options.mode.has_human() {
+        return Err(format!("--headless needs a mode without human players, not {}", options.mode.name()));
+    }
+
+    if options.host_port.is_some() || options.join_addr.is_some() {
+        if options.host_port.is_some() && options.join_addr.is_some() {
+            return Err("--host and --join can't be used together".to_string());
+        }
+        if mode.is_some() || options.replay_path.is_some() || options.record_path.is_some() {
+            return Err("netplay is always two players; --mode, --replay and --record don't apply".to_string());
+        }
+        if serve.is_some() || options.seed.is_some() {
+            return Err("--serve and --seed can't be used with netplay".to_string());
+        }
+        if options.headless || options.screenshot_path.is_some() || options.tui {
+            return Err("--headless, --screenshot and --tui can't be used with netplay".to_string());
+        }
+    }
+    if options.tui && (options.headless || options.screenshot_path.is_some()) {
+        return Err("--tui can't be used with --headless or --screenshot".to_string());
+    }
+    if options.mouse {
+        if !matches!(options.mode, Mode::VsAi | Mode::TwoPlayer) {
+            return Err(format!("--mouse needs a human left paddle, which --mode {} doesn't have", options.mode.name()));
+        }
+        if options.headless || options.screenshot_path.is_some() || options.tui {
+            return Err("--mouse needs a window, so it can't be used with --headless, --screenshot or --tui".to_string());
+        }
+    }
+    if (options.fullscreen || options.integer_scale) && (options.headless || options.screenshot_path.is_some() || options.tui) {
+        return Err("--fullscreen and --integer-scale need a window, so they can't be used with --headless, --screenshot or --tui".to_string());
+    }
+    if options.dev && (options.headless || options.screenshot_path.is_some() || options.tui) {
+        return Err("--dev needs a window, so it can't be used with --headless, --screenshot or --tui".to_string());
+    }
+    Ok(Command::Run(Box::new(options)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Options, String> {
+        match parse(line.split_whitespace().map(String::from))? {
+            Command::Run(options) => Ok(*options),
+            Command::Help => panic!("{:?} asked for help", line)
+        }
+    }
+
+    fn parse_error(line: &str) -> String {
+        match parse_line(line) {
+            Ok(_) => panic!("{:?} should be rejected", line),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn defaults_to_a_match_against_the_ai() {
+        let options = parse_line("").unwrap();
+        assert_eq!(options.mode, Mode::VsAi);
+        assert_eq!(options.difficulty, Difficulty::Medium);
+        assert_eq!(options.serve, Serve::Left);
+        assert_eq!(options.win_score, None);
+        assert!(!options.headless && !options.mouse && !options.integer_scale);
+    }
+
+    #[test]
+    fn every_flag_sets_its_own_option() {
+        let options = parse_line("--mode=two-player --difficulty hard --win-score 3 --serve random --seed 42 \
+                                  --config pong.toml --assets assets --scale 1.5 --fullscreen --integer-scale \
+                                  --show-fps --dev --mute --mouse --record out.replay --bindings keys.txt").unwrap();
+        assert_eq!(options.mode, Mode::TwoPlayer);
+        assert_eq!(options.difficulty, Difficulty::Hard);
+        assert_eq!(options.win_score, Some(3));
+        assert_eq!(options.serve, Serve::Random);
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.config_path.as_deref(), Some("pong.toml"));
+        assert_eq!(options.assets_dir.as_deref(), Some("assets"));
+        assert_eq!(options.scale, 1.5);
+        assert!(options.fullscreen && options.integer_scale && options.show_fps && options.dev);
+        assert!(options.mute && options.mouse);
+        assert!(!options.headless && !options.tui);
+        assert_eq!(options.record_path.as_deref(), Some("out.replay"));
+        assert_eq!(options.bindings_path.as_deref(), Some("keys.txt"));
+        assert_eq!(options.host_port, None);
+        assert_eq!(options.join_addr, None);
+        assert_eq!(parse_line("--join 10.0.0.2:7000").unwrap().join_addr.as_deref(), Some("10.0.0.2:7000"));
+        assert_eq!(parse_line("--host 7000").unwrap().host_port, Some(7000));
+        assert!(parse_line("--tui").unwrap().tui);
+        assert!(parse_line("--mode ai-vs-ai --headless --screenshot end.png").unwrap().headless);
+    }
+
+    #[test]
+    fn help_wins() {
+        assert!(matches!(parse(vec!["--mode".to_string(), "ai-vs-ai".to_string(), "-h".to_string()].into_iter()),
+                         Ok(Command::Help)));
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        assert_eq!(parse_error("--frobnicate"), "unknown argument `--frobnicate`");
+        assert_eq!(parse_error("--mode"), "--mode needs a value");
+        assert_eq!(parse_error("--mute=yes"), "--mute doesn't take a value");
+        assert_eq!(parse_error("--win-score 0"), "invalid value `0` for --win-score, expected a positive whole number");
+        assert_eq!(parse_error("--host port"), "invalid value `port` for --host, expected a port number");
+    }
+
+    #[test]
+    fn headless_needs_a_mode_without_humans() {
+        assert_eq!(parse_error("--headless"), "--headless needs a mode without human players, not vs-ai");
+        assert_eq!(parse_error("--headless --mode two-player"),
+                   "--headless needs a mode without human players, not two-player");
+        assert_eq!(parse_line("--headless --mode ai-vs-ai").unwrap().mode, Mode::AiVsAi);
+    }
+
+    #[test]
+    fn host_and_join_are_exclusive() {
+        assert_eq!(parse_error("--host 7000 --join 10.0.0.2:7000"), "--host and --join can't be used together");
+        assert_eq!(parse_error("--host 7000 --mode vs-ai"),
+                   "netplay is always two players; --mode, --replay and --record don't apply");
+    }
+
+    #[test]
+    fn replay_decides_the_mode() {
+        let options = parse_line("--replay match.replay").unwrap();
+        assert_eq!(options.mode, Mode::Replay);
+        assert_eq!(parse_line("--replay match.replay --mode replay").unwrap().mode, Mode::Replay);
+        assert_eq!(parse_error("--replay match.replay --mode two-player"), "--replay can't be used with --mode two-player");
+        assert_eq!(parse_error("--mode replay"), "--mode replay needs a --replay file");
+        assert_eq!(parse_error("--replay match.replay --seed 3"),
+                   "--serve, --seed and --win-score come from the recording when playing a replay");
+    }
+
+    #[test]
+    fn mouse_needs_a_human_left_paddle_and_a_window() {
+        assert_eq!(parse_error("--mouse --mode ai-vs-ai"),
+                   "--mouse needs a human left paddle, which --mode ai-vs-ai doesn't have");
+        assert_eq!(parse_error("--mouse --replay match.replay"),
+                   "--mouse needs a human left paddle, which --mode replay doesn't have");
+        assert_eq!(parse_error("--mouse --tui"),
+                   "--mouse needs a window, so it can't be used with --headless, --screenshot or --tui");
+    }
+}
@@ -0,0 +1,788 @@
+use std::fmt;
+use std::io::prelude::*;
+use std::fs::File;
+use game;
+use hash::Fnv;
+use synth;
+use theme;
+
+/// Every tuning value of a match. `GameConfig::default()` is the classic
+/// game; `GameConfig::load` reads overrides from a TOML file such as:
+///
+/// ```toml
+/// [ball]
+/// start_speed = 250
+///
+/// [board]
+/// win_score = 5
+/// ```
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    pub board_width: f32,
+    pub board_height: f32,
+    pub goal_height: f32,
+    pub win_score: u32,
+    /// Freeze after a paddle hit, in seconds.
+    pub hit_delay: f32,
+    /// Distance from the board edge to the paddle centre.
+    pub paddle_x_offset: f32,
+    pub paddle_width: f32,
+    pub paddle_height: f32,
+    pub paddle_max_speed: f32,
+    /// Fraction of its speed a paddle keeps after one second.
+    pub paddle_friction: f32,
+    /// How much of the paddle's speed carries over into the ball.
+    pub paddle_ball_influence: f32,
+    /// How strongly hitting off-centre angles the ball.
+    pub paddle_curve: f32,
+    /// Acceleration from a held key, or a stick pushed all the way.
+    pub player_paddle_accel: f32,
+    pub ball_radius: f32,
+    pub ball_max_speed: f32,
+    /// Steepest `dy / dx` the ball may travel at.
+    pub ball_max_slope: f32,
+    /// Speed multiplier applied on every paddle hit.
+    pub ball_speedup: f32,
+    pub ball_start_speed: f32,
+    pub ai: AiConfig,
+    pub graphics: GraphicsConfig,
+    pub effects: EffectsConfig,
+    pub audio: AudioConfig,
+    pub synth: SynthConfig,
+    pub gamepad: GamepadConfig,
+    pub power_ups: PowerUpConfig
+}
+
+#[derive(Clone, Debug)]
+pub struct AiConfig {
+    pub p_factor: f32,
+    pub i_factor: f32,
+    pub d_factor: f32,
+    pub max_accel: f32,
+    /// Gain and acceleration multiplier for the easy AI.
+    pub easy_slowdown: f32,
+    /// Gain and acceleration multiplier for the hard AI.
+    pub hard_speedup: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct GraphicsConfig {
+    /// Space between the board and the window edge.
+    pub board_padding: f32,
+    pub border_width: f32,
+    pub digit_line_size: f32,
+    pub digit_line_thickness: f32,
+    pub digit_spacing: f32,
+    pub net_width: f32,
+    pub score_padding: f32,
+    pub net_segments: u32,
+    /// Rounds the paddle corners; half the paddle width gives round ends.
+    pub paddle_corner_radius: f32,
+    /// Name of one of the color themes in `theme.rs`.
+    pub theme: String
+}
+
+#[derive(Clone, Debug)]
+pub struct EffectsConfig {
+    /// Turns the ball trail and all particles on or off.
+    pub enabled: bool,
+    /// Trail points kept at the ball's top speed; slower balls leave fewer.
+    pub trail_length: u32,
+    /// Sparks per wall or paddle hit.
+    pub spark_count: u32,
+    /// Particles in a goal explosion.
+    pub explosion_count: u32,
+    /// How long a particle lives, in seconds.
+    pub particle_lifetime: f32,
+    pub particle_speed: f32,
+    /// Strength of the screen shake, hit flash and ball squash; 0 turns
+    /// them off.
+    pub intensity: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioConfig {
+    /// Volumes from 0 to 1. The master volume scales the others.
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    /// How far hits are panned towards the side they happen on, from 0
+    /// (centred) to 1 (fully left or right at the board edges).
+    pub stereo_width: f32,
+    /// Pitch added to hit sounds at the ball's top speed; 0.5 is a fifth up.
+    pub max_pitch_rise: f32,
+    /// Copies of each sound that can play at once.
+    pub voices: u32,
+    /// "classic" for the sound files, or "synth" for the blips set up in
+    /// `[synth]`. Countdown beeps are always synthesized.
+    pub sound_pack: String
+}
+
+pub const SOUND_PACKS: &[&str] = &["classic", "synth"];
+
+/// A synthesized sound; see `synth::Blip`.
+#[derive(Clone, Debug)]
+pub struct BlipConfig {
+    /// "square" or "sine".
+    pub waveform: String,
+    pub frequency: f32,
+    /// Where the pitch slides to; the same as `frequency` for a steady tone.
+    pub end_frequency: f32,
+    /// In seconds.
+    pub duration: f32,
+    pub volume: f32
+}
+
+impl BlipConfig {
+    fn new(waveform: synth::Waveform, frequency: f32, end_frequency: f32, duration: f32, volume: f32) -> Self {
+        BlipConfig {
+            waveform: match waveform {
+                synth::Waveform::Square => "square",
+                synth::Waveform::Sine => "sine"
+            }.to_string(),
+            frequency,
+            end_frequency,
+            duration,
+            volume
+        }
+    }
+
+    pub fn blip(&self) -> synth::Blip {
+        synth::Blip {
+            waveform: synth::Waveform::from_name(&self.waveform).unwrap_or(synth::Waveform::Square),
+            frequency: self.frequency,
+            end_frequency: self.end_frequency,
+            duration: self.duration,
+            volume: self.volume
+        }
+    }
+}
+
+/// The sounds of the "synth" sound pack, plus the countdown beep.
+#[derive(Clone, Debug)]
+pub struct SynthConfig {
+    pub paddle: BlipConfig,
+    pub wall: BlipConfig,
+    pub goal: BlipConfig,
+    pub countdown: BlipConfig,
+    /// Octaves between a hit at the bottom of a paddle and one at the top,
+    /// with the synth sound pack.
+    pub paddle_pitch_spread: f32
+}
+
+/// Which gamepad axes and buttons do what, by their joystick driver
+/// numbers. The defaults suit Xbox-style pads.
+#[derive(Clone, Debug)]
+pub struct GamepadConfig {
+    pub enabled: bool,
+    /// Fraction of the stick's travel around the centre that is ignored.
+    pub deadzone: f32,
+    /// Vertical axis of the stick that steers the paddle.
+    pub stick_axis: u32,
+    /// Vertical axis of the D-pad, which steers the menus and the paddle at
+    /// full speed.
+    pub dpad_axis: u32,
+    pub confirm_button: u32,
+    pub pause_button: u32
+}
+
+/// Power-ups the ball picks up on its way across, for whoever hit it last.
+#[derive(Clone, Debug)]
+pub struct PowerUpConfig {
+    pub enabled: bool,
+    /// Seconds between spawns.
+    pub spawn_interval: f32,
+    /// Most power-ups waiting on the board at once.
+    pub max_active: u32,
+    pub radius: f32,
+    /// How long an effect lasts, in seconds.
+    pub duration: f32,
+    /// Paddle height multiplier for the collector.
+    pub paddle_grow: f32,
+    /// Paddle height multiplier for the collector's opponent.
+    pub paddle_shrink: f32,
+    /// Ball speed multiplier.
+    pub speed_burst: f32,
+    /// Height multiplier for the goal the opponent aims at.
+    pub goal_shrink: f32
+}
+
+impl GraphicsConfig {
+    pub fn theme(&self) -> theme::Theme {
+        theme::Theme::from_name(&self.theme)
+            .unwrap_or_else(|| theme::Theme::from_name(theme::DEFAULT_THEME).unwrap())
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            board_width: 600.,
+            board_height: 300.,
+            goal_height: 240.,
+            win_score: 10,
+            hit_delay: 0.05,
+            paddle_x_offset: 10.,
+            paddle_width: 10.,
+            paddle_height: 60.,
+            paddle_max_speed: 500.,
+            paddle_friction: 0.005,
+            paddle_ball_influence: 0.3,
+            paddle_curve: 0.5,
+            player_paddle_accel: 2000.,
+            ball_radius: 5.,
+            ball_max_speed: 800.,
+            ball_max_slope: 1.,
+            ball_speedup: 1.05,
+            ball_start_speed: 300.,
+            ai: AiConfig {
+                p_factor: 40.,
+                i_factor: 0.1,
+                d_factor: 2.,
+                max_accel: 1800.,
+                easy_slowdown: 0.4,
+                hard_speedup: 1.5
+            },
+            graphics: GraphicsConfig {
+                board_padding: 10.,
+                border_width: 2.,
+                digit_line_size: 20.,
+                digit_line_thickness: 5.,
+                digit_spacing: 10.,
+                net_width: 1.,
+                score_padding: 20.,
+                net_segments: 20,
+                paddle_corner_radius: 5.,
+                theme: theme::DEFAULT_THEME.to_string()
+            },
+            effects: EffectsConfig {
+                enabled: true,
+                trail_length: 12,
+                spark_count: 8,
+                explosion_count: 60,
+                particle_lifetime: 0.6,
+                particle_speed: 150.,
+                intensity: 1.
+            },
+            audio: AudioConfig {
+                master_volume: 1.,
+                sfx_volume: 1.,
+                stereo_width: 0.8,
+                max_pitch_rise: 0.5,
+                voices: 4,
+                sound_pack: "classic".to_string()
+            },
+            synth: SynthConfig {
+                paddle: BlipConfig::new(synth::Waveform::Square, 440., 440., 0.08, 0.3),
+                wall: BlipConfig::new(synth::Waveform::Square, 220., 220., 0.05, 0.25),
+                goal: BlipConfig::new(synth::Waveform::Square, 330., 110., 0.4, 0.3),
+                countdown: BlipConfig::new(synth::Waveform::Sine, 660., 660., 0.12, 0.4),
+                paddle_pitch_spread: 1.
+            },
+            gamepad: GamepadConfig {
+                enabled: true,
+                deadzone: 0.15,
+                stick_axis: 1,
+                dpad_axis: 7,
+                confirm_button: 0,
+                pause_button: 7
+            },
+            power_ups: PowerUpConfig {
+                enabled: false,
+                spawn_interval: 6.,
+                max_active: 2,
+                radius: 12.,
+                duration: 8.,
+                paddle_grow: 1.5,
+                paddle_shrink: 0.6,
+                speed_burst: 1.5,
+                goal_shrink: 0.5
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+            None => write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+enum Field<'a> {
+    Float(&'a mut f32),
+    Integer(&'a mut u32),
+    Bool(&'a mut bool),
+    Text(&'a mut String)
+}
+
+impl GameConfig {
+    /// Every settable value as `(section, key, field)`.
+    fn fields(&mut self) -> Vec<(&'static str, &'static str, Field<'_>)> {
+        vec![
+            ("board", "width", Field::Float(&mut self.board_width)),
+            ("board", "height", Field::Float(&mut self.board_height)),
+            ("board", "goal_height", Field::Float(&mut self.goal_height)),
+            ("board", "win_score", Field::Integer(&mut self.win_score)),
+            ("board", "hit_delay", Field::Float(&mut self.hit_delay)),
+            ("paddle", "x_offset", Field::Float(&mut self.paddle_x_offset)),
+            ("paddle", "width", Field::Float(&mut self.paddle_width)),
+            ("paddle", "height", Field::Float(&mut self.paddle_height)),
+            ("paddle", "max_speed", Field::Float(&mut self.paddle_max_speed)),
+            ("paddle", "friction", Field::Float(&mut self.paddle_friction)),
+            ("paddle", "ball_influence", Field::Float(&mut self.paddle_ball_influence)),
+            ("paddle", "curve", Field::Float(&mut self.paddle_curve)),
+            ("paddle", "player_accel", Field::Float(&mut self.player_paddle_accel)),
+            ("ball", "radius", Field::Float(&mut self.ball_radius)),
+            ("ball", "max_speed", Field::Float(&mut self.ball_max_speed)),
+            ("ball", "max_slope", Field::Float(&mut self.ball_max_slope)),
+            ("ball", "speedup", Field::Float(&mut self.ball_speedup)),
+            ("ball", "start_speed", Field::Float(&mut self.ball_start_speed)),
+            ("ai", "p_factor", Field::Float(&mut self.ai.p_factor)),
+            ("ai", "i_factor", Field::Float(&mut self.ai.i_factor)),
+            ("ai", "d_factor", Field::Float(&mut self.ai.d_factor)),
+            ("ai", "max_accel", Field::Float(&mut self.ai.max_accel)),
+            ("ai", "easy_slowdown", Field::Float(&mut self.ai.easy_slowdown)),
+            ("ai", "hard_speedup", Field::Float(&mut self.ai.hard_speedup)),
+            ("graphics", "board_padding", Field::Float(&mut self.graphics.board_padding)),
+            ("graphics", "border_width", Field::Float(&mut self.graphics.border_width)),
+            ("graphics", "digit_line_size", Field::Float(&mut self.graphics.digit_line_size)),
+            ("graphics", "digit_line_thickness", Field::Float(&mut self.graphics.digit_line_thickness)),
+            ("graphics", "digit_spacing", Field::Float(&mut self.graphics.digit_spacing)),
+            ("graphics", "net_width", Field::Float(&mut self.graphics.net_width)),
+            ("graphics", "score_padding", Field::Float(&mut self.graphics.score_padding)),
+            ("graphics", "net_segments", Field::Integer(&mut self.graphics.net_segments)),
+            ("graphics", "paddle_corner_radius", Field::Float(&mut self.graphics.paddle_corner_radius)),
+            ("graphics", "theme", Field::Text(&mut self.graphics.theme)),
+            ("effects", "enabled", Field::Bool(&mut self.effects.enabled)),
+            ("effects", "trail_length", Field::Integer(&mut self.effects.trail_length)),
+            ("effects", "spark_count", Field::Integer(&mut self.effects.spark_count)),
+            ("effects", "explosion_count", Field::Integer(&mut self.effects.explosion_count)),
+            ("effects", "particle_lifetime", Field::Float(&mut self.effects.particle_lifetime)),
+            ("effects", "particle_speed", Field::Float(&mut self.effects.particle_speed)),
+            ("effects", "intensity", Field::Float(&mut self.effects.intensity)),
+            ("audio", "master_volume", Field::Float(&mut self.audio.master_volume)),
+            ("audio", "sfx_volume", Field::Float(&mut self.audio.sfx_volume)),
+            ("audio", "stereo_width", Field::Float(&mut self.audio.stereo_width)),
+            ("audio", "max_pitch_rise", Field::Float(&mut self.audio.max_pitch_rise)),
+            ("audio", "voices", Field::Integer(&mut self.audio.voices)),
+            ("audio", "sound_pack", Field::Text(&mut self.audio.sound_pack)),
+            ("synth", "paddle_waveform", Field::Text(&mut self.synth.paddle.waveform)),
+            ("synth", "paddle_frequency", Field::Float(&mut self.synth.paddle.frequency)),
+            ("synth", "paddle_end_frequency", Field::Float(&mut self.synth.paddle.end_frequency)),
+            ("synth", "paddle_duration", Field::Float(&mut self.synth.paddle.duration)),
+            ("synth", "paddle_volume", Field::Float(&mut self.synth.paddle.volume)),
+            ("synth", "wall_waveform", Field::Text(&mut self.synth.wall.waveform)),
+            ("synth", "wall_frequency", Field::Float(&mut self.synth.wall.frequency)),
+            ("synth", "wall_end_frequency", Field::Float(&mut self.synth.wall.end_frequency)),
+            ("synth", "wall_duration", Field::Float(&mut self.synth.wall.duration)),
+            ("synth", "wall_volume", Field::Float(&mut self.synth.wall.volume)),
+            ("synth", "goal_waveform", Field::Text(&mut self.synth.goal.waveform)),
+            ("synth", "goal_frequency", Field::Float(&mut self.synth.goal.frequency)),
+            ("synth", "goal_end_frequency", Field::Float(&mut self.synth.goal.end_frequency)),
+            ("synth", "goal_duration", Field::Float(&mut self.synth.goal.duration)),
+            ("synth", "goal_volume", Field::Float(&mut self.synth.goal.volume)),
+            ("synth", "countdown_waveform", Field::Text(&mut self.synth.countdown.waveform)),
+            ("synth", "countdown_frequency", Field::Float(&mut self.synth.countdown.frequency)),
+            ("synth", "countdown_end_frequency", Field::Float(&mut self.synth.countdown.end_frequency)),
+            ("synth", "countdown_duration", Field::Float(&mut self.synth.countdown.duration)),
+            ("synth", "countdown_volume", Field::Float(&mut self.synth.countdown.volume)),
+            ("synth", "paddle_pitch_spread", Field::Float(&mut self.synth.paddle_pitch_spread)),
+            ("gamepad", "enabled", Field::Bool(&mut self.gamepad.enabled)),
+            ("gamepad", "deadzone", Field::Float(&mut self.gamepad.deadzone)),
+            ("gamepad", "stick_axis", Field::Integer(&mut self.gamepad.stick_axis)),
+            ("gamepad", "dpad_axis", Field::Integer(&mut self.gamepad.dpad_axis)),
+            ("gamepad", "confirm_button", Field::Integer(&mut self.gamepad.confirm_button)),
+            ("gamepad", "pause_button", Field::Integer(&mut self.gamepad.pause_button)),
+            ("powerups", "enabled", Field::Bool(&mut self.power_ups.enabled)),
+            ("powerups", "spawn_interval", Field::Float(&mut self.power_ups.spawn_interval)),
+            ("powerups", "max_active", Field::Integer(&mut self.power_ups.max_active)),
+            ("powerups", "radius", Field::Float(&mut self.power_ups.radius)),
+            ("powerups", "duration", Field::Float(&mut self.power_ups.duration)),
+            ("powerups", "paddle_grow", Field::Float(&mut self.power_ups.paddle_grow)),
+            ("powerups", "paddle_shrink", Field::Float(&mut self.power_ups.paddle_shrink)),
+            ("powerups", "speed_burst", Field::Float(&mut self.power_ups.speed_burst)),
+            ("powerups", "goal_shrink", Field::Float(&mut self.power_ups.goal_shrink))
+        ]
+    }
+
+    /// Reads a config file on top of the defaults. Only the subset of TOML
+    /// needed here is understood: `[section]` headers, `key = value` pairs,
+    /// numbers, booleans, double-quoted strings without escapes and `#`
+    /// comments.
+    pub fn load(path: &str) -> Result<GameConfig, ConfigError> {
+        let error = |line: Option<usize>, message: String| ConfigError {
+            path: path.to_string(),
+            line,
+            message
+        };
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| error(None, e.to_string()))?;
+
+        let mut config = GameConfig::default();
+        let mut section = String::new();
+        {
+            let mut fields = config.fields();
+            for (i, line) in contents.lines().enumerate() {
+                let line_error = |message: String| error(Some(i + 1), message);
+                let line = line.split('#').next().unwrap().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if line.starts_with('[') {
+                    if !line.ends_with(']') {
+                        return Err(line_error("unterminated section header".to_string()));
+                    }
+                    section = line[1..line.len() - 1].trim().to_string();
+                    if !fields.iter().any(|&(s, _, _)| s == section) {
+                        return Err(line_error(format!("unknown section [{}]", section)));
+                    }
+                    continue;
+                }
+                let mut parts = line.splitn(2, '=').map(|part| part.trim());
+                let (key, value) = match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if !key.is_empty() => (key, value),
+                    _ => return Err(line_error("expected `key = value`".to_string()))
+                };
+                let field = fields.iter_mut()
+                    .find(|&&mut (s, k, _)| s == section && k == key)
+                    .map(|&mut (_, _, ref mut field)| field);
+                match field {
+                    Some(&mut Field::Float(ref mut target)) => {
+                        **target = value.parse().map_err(|_| {
+                            line_error(format!("`{}` must be a number, not `{}`", key, value))
+                        })?;
+                    },
+                    Some(&mut Field::Integer(ref mut target)) => {
+                        **target = value.parse().map_err(|_| {
+                            line_error(format!("`{}` must be a whole number, not `{}`", key, value))
+                        })?;
+                    },
+                    Some(&mut Field::Bool(ref mut target)) => {
+                        **target = value.parse().map_err(|_| {
+                            line_error(format!("`{}` must be true or false, not `{}`", key, value))
+                        })?;
+                    },
+                    Some(&mut Field::Text(ref mut target)) => {
+                        if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                            return Err(line_error(format!("`{}` must be a quoted string, not `{}`", key, value)));
+                        }
+                        **target = value[1..value.len() - 1].to_string();
+                    },
+                    None if section.is_empty() => {
+                        return Err(line_error(format!("`{}` must be inside a section such as [board]", key)));
+                    },
+                    None => {
+                        let known: Vec<_> = fields.iter()
+                            .filter(|&&(s, _, _)| s == section)
+                            .map(|&(_, k, _)| k)
+                            .collect();
+                        return Err(line_error(format!("unknown key `{}` in [{}], expected one of: {}",
+                            key, section, known.join(", "))));
+                    }
+                }
+            }
+        }
+        config.validate().map_err(|message| error(None, message))?;
+        Ok(config)
+    }
+
+    /// Checks that the values make a playable board.
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("board.width", self.board_width),
+            ("board.height", self.board_height),
+            ("board.goal_height", self.goal_height),
+            ("paddle.width", self.paddle_width),
+            ("paddle.height", self.paddle_height),
+            ("paddle.max_speed", self.paddle_max_speed),
+            ("paddle.player_accel", self.player_paddle_accel),
+            ("ball.radius", self.ball_radius),
+            ("ball.max_speed", self.ball_max_speed),
+            ("ball.max_slope", self.ball_max_slope),
+            ("ball.speedup", self.ball_speedup),
+            ("ball.start_speed", self.ball_start_speed),
+            ("ai.max_accel", self.ai.max_accel),
+            ("ai.easy_slowdown", self.ai.easy_slowdown),
+            ("ai.hard_speedup", self.ai.hard_speedup),
+            ("synth.paddle_frequency", self.synth.paddle.frequency),
+            ("synth.paddle_end_frequency", self.synth.paddle.end_frequency),
+            ("synth.paddle_duration", self.synth.paddle.duration),
+            ("synth.wall_frequency", self.synth.wall.frequency),
+            ("synth.wall_end_frequency", self.synth.wall.end_frequency),
+            ("synth.wall_duration", self.synth.wall.duration),
+            ("synth.goal_frequency", self.synth.goal.frequency),
+            ("synth.goal_end_frequency", self.synth.goal.end_frequency),
+            ("synth.goal_duration", self.synth.goal.duration),
+            ("synth.countdown_frequency", self.synth.countdown.frequency),
+            ("synth.countdown_end_frequency", self.synth.countdown.end_frequency),
+            ("synth.countdown_duration", self.synth.countdown.duration)
+        ];
+        for &(name, value) in positive.iter() {
+            if !(value > 0. && value.is_finite()) {
+                return Err(format!("{} must be greater than 0, got {}", name, value));
+            }
+        }
+        let non_negative = [
+            ("board.hit_delay", self.hit_delay),
+            ("paddle.x_offset", self.paddle_x_offset),
+            ("paddle.ball_influence", self.paddle_ball_influence),
+            ("paddle.curve", self.paddle_curve),
+            ("ai.p_factor", self.ai.p_factor),
+            ("ai.i_factor", self.ai.i_factor),
+            ("ai.d_factor", self.ai.d_factor),
+            ("graphics.board_padding", self.graphics.board_padding),
+            ("graphics.border_width", self.graphics.border_width),
+            ("graphics.digit_line_size", self.graphics.digit_line_size),
+            ("graphics.digit_line_thickness", self.graphics.digit_line_thickness),
+            ("graphics.digit_spacing", self.graphics.digit_spacing),
+            ("graphics.net_width", self.graphics.net_width),
+            ("graphics.score_padding", self.graphics.score_padding),
+            ("graphics.paddle_corner_radius", self.graphics.paddle_corner_radius),
+            ("effects.particle_lifetime", self.effects.particle_lifetime),
+            ("effects.particle_speed", self.effects.particle_speed),
+            ("effects.intensity", self.effects.intensity)
+        ];
+        for &(name, value) in non_negative.iter() {
+            if !(value >= 0. && value.is_finite()) {
+                return Err(format!("{} must not be negative, got {}", name, value));
+            }
+        }
+        if self.win_score == 0 {
+            return Err("board.win_score must be at least 1".to_string());
+        }
+        if self.graphics.net_segments == 0 {
+            return Err("graphics.net_segments must be at least 1".to_string());
+        }
+        if theme::Theme::from_name(&self.graphics.theme).is_none() {
+            return Err(format!("unknown graphics.theme \"{}\", expected one of: {}",
+                self.graphics.theme, theme::Theme::names().join(", ")));
+        }
+        let unit = [
+            ("audio.master_volume", self.audio.master_volume),
+            ("audio.sfx_volume", self.audio.sfx_volume),
+            ("audio.stereo_width", self.audio.stereo_width),
+            ("synth.paddle_volume", self.synth.paddle.volume),
+            ("synth.wall_volume", self.synth.wall.volume),
+            ("synth.goal_volume", self.synth.goal.volume),
+            ("synth.countdown_volume", self.synth.countdown.volume)
+        ];
+        for &(name, value) in unit.iter() {
+            if !(0. ..=1.).contains(&value) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        if !(self.audio.max_pitch_rise >= 0. && self.audio.max_pitch_rise.is_finite()) {
+            return Err(format!("audio.max_pitch_rise must not be negative, got {}", self.audio.max_pitch_rise));
+        }
+        if self.audio.voices == 0 {
+            return Err("audio.voices must be at least 1".to_string());
+        }
+        if !SOUND_PACKS.contains(&self.audio.sound_pack.as_str()) {
+            return Err(format!("unknown audio.sound_pack \"{}\", expected one of: {}",
+                self.audio.sound_pack, SOUND_PACKS.join(", ")));
+        }
+        let blips = [
+            ("paddle", &self.synth.paddle),
+            ("wall", &self.synth.wall),
+            ("goal", &self.synth.goal),
+            ("countdown", &self.synth.countdown)
+        ];
+        for &(name, blip) in blips.iter() {
+            if synth::Waveform::from_name(&blip.waveform).is_none() {
+                return Err(format!("unknown synth.{}_waveform \"{}\", expected square or sine",
+                    name, blip.waveform));
+            }
+        }
+        if !(self.synth.paddle_pitch_spread >= 0. && self.synth.paddle_pitch_spread.is_finite()) {
+            return Err(format!("synth.paddle_pitch_spread must not be negative, got {}",
+                self.synth.paddle_pitch_spread));
+        }
+        if !(0. ..1.).contains(&self.gamepad.deadzone) {
+            return Err(format!("gamepad.deadzone must be at least 0 and below 1, got {}", self.gamepad.deadzone));
+        }
+        let power_ups = &self.power_ups;
+        let positive = [
+            ("powerups.spawn_interval", power_ups.spawn_interval),
+            ("powerups.radius", power_ups.radius),
+            ("powerups.duration", power_ups.duration),
+            ("powerups.paddle_grow", power_ups.paddle_grow),
+            ("powerups.paddle_shrink", power_ups.paddle_shrink),
+            ("powerups.speed_burst", power_ups.speed_burst),
+            ("powerups.goal_shrink", power_ups.goal_shrink)
+        ];
+        for &(name, value) in positive.iter() {
+            if !(value > 0. && value.is_finite()) {
+                return Err(format!("{} must be greater than 0, got {}", name, value));
+            }
+        }
+        if power_ups.enabled && 2. * power_ups.radius >= self.board_height {
+            return Err(format!("powerups.radius ({}) doesn't fit on a board {} high",
+                power_ups.radius, self.board_height));
+        }
+        if !(self.paddle_friction > 0. && self.paddle_friction <= 1.) {
+            return Err(format!("paddle.friction must be in (0, 1], got {}", self.paddle_friction));
+        }
+        if self.paddle_height > self.board_height {
+            return Err(format!("paddle.height ({}) is taller than board.height ({})",
+                self.paddle_height, self.board_height));
+        }
+        if self.goal_height > self.board_height {
+            return Err(format!("board.goal_height ({}) is taller than board.height ({})",
+                self.goal_height, self.board_height));
+        }
+        if 2. * self.ball_radius >= self.board_height {
+            return Err(format!("ball.radius ({}) doesn't fit on a board {} high",
+                self.ball_radius, self.board_height));
+        }
+        if self.paddle_x_offset + self.paddle_width / 2. >= self.board_width / 2. {
+            return Err(format!("paddle.x_offset ({}) puts the paddles past the middle of the board",
+                self.paddle_x_offset));
+        }
+        if self.ball_start_speed > self.ball_max_speed {
+            return Err(format!("ball.start_speed ({}) is above ball.max_speed ({})",
+                self.ball_start_speed, self.ball_max_speed));
+        }
+        Ok(())
+    }
+
+    /// The share of its speed a coasting paddle keeps over one tick.
+    /// `paddle_friction` is per second, so this is its `TICK_RATE`th root,
+    /// worked out in f64 so the rounded result doesn't depend on the
+    /// platform's f32 `powf`.
+    pub fn paddle_friction_per_tick(&self) -> f32 {
+        f64::from(self.paddle_friction).powf(1. / f64::from(game::TICK_RATE)) as f32
+    }
+
+    /// Hashes every value that steers the simulation, so a replay can tell
+    /// whether it is being played back with the settings it was recorded
+    /// with. The win score, looks, sound and controls are left out.
+    pub fn simulation_hash(&self) -> u64 {
+        let power_ups = &self.power_ups;
+        let values = [
+            self.board_width, self.board_height, self.goal_height, self.hit_delay,
+            self.paddle_x_offset, self.paddle_width, self.paddle_height, self.paddle_max_speed,
+            self.paddle_friction, self.paddle_ball_influence, self.paddle_curve, self.player_paddle_accel,
+            self.ball_radius, self.ball_max_speed, self.ball_max_slope, self.ball_speedup, self.ball_start_speed,
+            self.ai.p_factor, self.ai.i_factor, self.ai.d_factor, self.ai.max_accel,
+            self.ai.easy_slowdown, self.ai.hard_speedup,
+            power_ups.spawn_interval, power_ups.radius, power_ups.duration, power_ups.paddle_grow,
+            power_ups.paddle_shrink, power_ups.speed_burst, power_ups.goal_shrink
+        ];
+        let mut hash = Fnv::new();
+        for value in values.iter() {
+            hash.write_u64(u64::from(value.to_bits()));
+        }
+        hash.write_u64(power_ups.enabled as u64);
+        hash.write_u64(u64::from(power_ups.max_active));
+        hash.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn load_text(name: &str, text: &str) -> Result<GameConfig, ConfigError> {
+        let path = env::temp_dir().join(format!("pong-test-{}-{}", process::id(), name));
+        fs::write(&path, text).unwrap();
+        let config = GameConfig::load(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    fn load_error(name: &str, text: &str) -> ConfigError {
+        match load_text(name, text) {
+            Ok(_) => panic!("{:?} should not load", text),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn loads_the_example_config() {
+        let config = GameConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/pong.toml")).unwrap();
+        let default = GameConfig::default();
+        assert_eq!(config.simulation_hash(), default.simulation_hash());
+        assert_eq!(config.win_score, default.win_score);
+    }
+
+    #[test]
+    fn overrides_only_the_given_keys() {
+        let config = load_text("overrides", "# Faster\n[ball]\nstart_speed = 450 # px/s\n\n[audio]\nsound_pack = \"synth\"\n").unwrap();
+        assert_eq!(config.ball_start_speed, 450.);
+        assert_eq!(config.audio.sound_pack, "synth");
+        assert_eq!(config.ball_max_speed, GameConfig::default().ball_max_speed);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let error = load_error("parse", "[ball]\nradius = 5\n\nstart_speed = fast\n");
+        assert_eq!(error.line, Some(4));
+        assert_eq!(error.message, "`start_speed` must be a number, not `fast`");
+        assert!(error.to_string().ends_with(":4: `start_speed` must be a number, not `fast`"));
+        assert_eq!(load_error("header", "[ball\n").line, Some(1));
+        assert_eq!(load_error("section", "[pinball]\n").message, "unknown section [pinball]");
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let error = load_error("unknown", "[ball]\nspin = 3\n");
+        assert_eq!(error.line, Some(2));
+        assert!(error.message.starts_with("unknown key `spin` in [ball], expected one of: "));
+        assert!(error.message.contains("start_speed"));
+    }
+
+    #[test]
+    fn keys_need_a_section() {
+        let error = load_error("sectionless", "radius = 5\n");
+        assert_eq!(error.line, Some(1));
+        assert_eq!(error.message, "`radius` must be inside a section such as [board]");
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let error = load_error("range", "[ball]\nradius = 0\n");
+        assert_eq!(error.line, None);
+        assert_eq!(error.message, "ball.radius must be greater than 0, got 0");
+        let error = load_error("volume", "[audio]\nsfx_volume = 1.5\n");
+        assert_eq!(error.message, "audio.sfx_volume must be between 0 and 1, got 1.5");
+        let error = load_error("power-ups", "[powerups]\nduration = -1\n");
+        assert_eq!(error.message, "powerups.duration must be greater than 0, got -1");
+    }
+
+    #[test]
+    fn power_up_size_only_matters_when_they_are_on() {
+        let mut config = GameConfig {
+            board_height: 20.,
+            goal_height: 10.,
+            paddle_height: 10.,
+            ..GameConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+        config.power_ups.enabled = true;
+        assert!(config.validate().unwrap_err().starts_with("powerups.radius"));
+    }
+
+    #[test]
+    fn friction_per_tick_compounds_to_the_per_second_value() {
+        let config = GameConfig::default();
+        let per_tick = config.paddle_friction_per_tick();
+        let per_second = (0..game::TICK_RATE).fold(1f64, |kept, _| kept * f64::from(per_tick));
+        assert!((per_second / f64::from(config.paddle_friction) - 1.).abs() < 1e-4);
+        let config = GameConfig { paddle_friction: 1., ..GameConfig::default() };
+        assert_eq!(config.paddle_friction_per_tick(), 1.);
+    }
+}
@@ -0,0 +1,467 @@
+use game;
+use graphics::{Canvas, Shape};
+use rng::Rng;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use theme;
+
+// Fraction of its speed a particle keeps after one second.
+const PARTICLE_DRAG: f32 = 0.1;
+// Sparks fly off within this angle either side of the ball's new direction.
+const SPARK_SPREAD: f32 = PI / 3.;
+// Explosion particles live longer than sparks by this factor.
+const EXPLOSION_LIFETIME: f32 = 1.5;
+// Furthest the camera moves at full trauma, in board units.
+const SHAKE_DISTANCE: f32 = 8.;
+// Trauma lost per second. Shake goes with its square, so it eases out.
+const SHAKE_DECAY: f32 = 2.;
+// Trauma added by a goal, and by a hit at the ball's top speed.
+const GOAL_TRAUMA: f32 = 0.8;
+const HIT_TRAUMA: f32 = 0.4;
+const FLASH_TIME: f32 = 0.15;
+const SQUASH_TIME: f32 = 0.12;
+// How much narrower the ball gets along the hit direction at full squash.
+const SQUASH_AMOUNT: f32 = 0.4;
+
+/// Which theme color a particle takes, looked up when it's drawn so that
+/// switching themes recolors particles already in flight.
+#[derive(Copy, Clone, Debug)]
+enum Tint {
+    Ball,
+    Paddle(game::Side),
+    Score(game::Side)
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    dx: f32,
+    dy: f32,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+    tint: Tint
+}
+
+/// Purely visual state that follows the board: the ball trail, the
+/// particles spawned by board events, and the shake, flash and squash on
+/// hits. None of it feeds back into the simulation, so it uses its own random
+/// generator.
+pub struct Effects {
+    rng: Rng,
+    trail: VecDeque<(f32, f32)>,
+    particles: Vec<Particle>,
+    trauma: f32,
+    shake: (f32, f32),
+    lhs_flash: f32,
+    rhs_flash: f32,
+    // Seconds left and whether the hit was on a horizontal wall.
+    squash: Option<(f32, bool)>
+}
+
+impl Effects {
+    pub fn new() -> Self {
+        Effects {
+            rng: Rng::new(0),
+            trail: VecDeque::new(),
+            particles: Vec::new(),
+            trauma: 0.,
+            shake: (0., 0.),
+            lhs_flash: 0.,
+            rhs_flash: 0.,
+            squash: None
+        }
+    }
+
+    pub fn handle_event(&mut self, board: &game::Board, event: &game::Event) {
+        self.handle_juice(board, event);
+        let config = &board.config.effects;
+        if !config.enabled {
+            return;
+        }
+        match *event {
+            game::Event::PaddleHit(side, impact, _) => {
+                self.burst(board, impact, config.spark_count, Tint::Paddle(side));
+            },
+            game::Event::WallHit(impact) => {
+                self.burst(board, impact, config.spark_count, Tint::Ball);
+            },
+            game::Event::Goal(scorer, impact) => {
+                self.trail.clear();
+                for _ in 0..config.explosion_count {
+                    let angle = 2. * PI * self.rng.next_f32();
+                    let speed = config.particle_speed * (0.5 + 1.5 * self.rng.next_f32());
+                    let lifetime = config.particle_lifetime * EXPLOSION_LIFETIME * (0.5 + self.rng.next_f32());
+                    let size = board.config.ball_radius * (0.5 + self.rng.next_f32());
+                    self.spawn((impact.x, impact.y), angle, speed, size, lifetime, Tint::Score(scorer));
+                }
+            },
+            game::Event::PowerUp(side, _, impact) => {
+                self.burst(board, impact, 2 * config.spark_count, Tint::Paddle(side));
+            }
+        }
+    }
+
+    fn handle_juice(&mut self, board: &game::Board, event: &game::Event) {
+        if board.config.effects.intensity <= 0. {
+            return;
+        }
+        let hit_trauma = |impact: game::Impact| HIT_TRAUMA * (impact.speed() / board.config.ball_max_speed).min(1.);
+        match *event {
+            game::Event::PaddleHit(side, impact, _) => {
+                self.trauma += hit_trauma(impact);
+                match side {
+                    game::Side::Lhs => self.lhs_flash = FLASH_TIME,
+                    game::Side::Rhs => self.rhs_flash = FLASH_TIME
+                }
+                self.squash = Some((SQUASH_TIME, false));
+            },
+            game::Event::WallHit(impact) => {
+                self.trauma += hit_trauma(impact);
+                // Goal borders are vertical; the top and bottom walls are
+                // the only place the ball touches near those edges.
+                let radius = board.config.ball_radius;
+                let horizontal = impact.y <= 1.5 * radius || impact.y >= board.height - 1.5 * radius;
+                self.squash = Some((SQUASH_TIME, horizontal));
+            },
+            game::Event::Goal(..) => {
+                self.trauma += GOAL_TRAUMA;
+                self.squash = None;
+            },
+            game::Event::PowerUp(side, ..) => match side {
+                game::Side::Lhs => self.lhs_flash = FLASH_TIME,
+                game::Side::Rhs => self.rhs_flash = FLASH_TIME
+            }
+        }
+        self.trauma = self.trauma.min(1.);
+    }
+
+    /// Sparks thrown along the ball's new direction.
+    fn burst(&mut self, board: &game::Board, impact: game::Impact, count: u32, tint: Tint) {
+        let config = &board.config.effects;
+        let heading = impact.dy.atan2(impact.dx);
+        // Faster hits throw sparks further.
+        let strength = (impact.speed() / board.config.ball_start_speed).max(1.).sqrt();
+        for _ in 0..count {
+            let angle = heading + SPARK_SPREAD * (2. * self.rng.next_f32() - 1.);
+            let speed = config.particle_speed * strength * (0.5 + 0.5 * self.rng.next_f32());
+            let lifetime = config.particle_lifetime * (0.5 + 0.5 * self.rng.next_f32());
+            let size = board.config.ball_radius * 0.8;
+            self.spawn((impact.x, impact.y), angle, speed, size, lifetime, tint);
+        }
+    }
+
+    fn spawn(&mut self, origin: (f32, f32), angle: f32, speed: f32, size: f32, lifetime: f32, tint: Tint) {
+        self.particles.push(Particle {
+            x: origin.0,
+            y: origin.1,
+            dx: speed * angle.cos(),
+            dy: speed * angle.sin(),
+            size,
+            age: 0.,
+            lifetime,
+            tint
+        });
+    }
+
+    /// Advances the particles and records the ball position for the trail.
+    pub fn update(&mut self, board: &game::Board, dt: f32) {
+        let config = &board.config.effects;
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.);
+        let shake = SHAKE_DISTANCE * config.intensity * self.trauma * self.trauma;
+        self.shake = (
+            shake * (2. * self.rng.next_f32() - 1.),
+            shake * (2. * self.rng.next_f32() - 1.)
+        );
+        self.lhs_flash = (self.lhs_flash - dt).max(0.);
+        self.rhs_flash = (self.rhs_flash - dt).max(0.);
+        self.squash = self.squash
+            .map(|(left, horizontal)| (left - dt, horizontal))
+            .filter(|&(left, _)| left > 0.);
+        if !config.enabled {
+            self.trail.clear();
+            self.particles.clear();
+            return;
+        }
+        let drag = PARTICLE_DRAG.powf(dt);
+        for particle in self.particles.iter_mut() {
+            particle.x += particle.dx * dt;
+            particle.y += particle.dy * dt;
+            particle.dx *= drag;
+            particle.dy *= drag;
+            particle.age += dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+
+        let ball = &board.ball;
+        let centre = (ball.bound.x + ball.bound.width / 2., ball.bound.y + ball.bound.height / 2.);
+        // A jump further than the ball can travel in one step is a new serve
+        // or a rewind, which the trail shouldn't connect across.
+        if let Some(&(x, y)) = self.trail.front() {
+            if (centre.0 - x).hypot(centre.1 - y) > 2. * board.config.ball_max_speed * dt {
+                self.trail.clear();
+            }
+        }
+        self.trail.push_front(centre);
+        self.trail.truncate(config.trail_length as usize + 1);
+    }
+
+    /// How far to move the camera this frame, in board units. Only backends
+    /// that draw at pixel precision apply it.
+    pub fn shake(&self) -> (f32, f32) {
+        self.shake
+    }
+
+    /// How far the paddle on `side` is blended towards its flash colour, from 0
+    /// to 1.
+    pub fn flash(&self, board: &game::Board, side: game::Side) -> f32 {
+        let left = match side {
+            game::Side::Lhs => self.lhs_flash,
+            game::Side::Rhs => self.rhs_flash
+        };
+        (board.config.effects.intensity * left / FLASH_TIME).min(1.)
+    }
+
+    /// The ball's bounds squashed along the last hit and stretched across
+    /// it, keeping the same centre.
+    pub fn ball_rect(&self, board: &game::Board, bound: game::Rect) -> game::Rect {
+        let (left, horizontal) = match self.squash {
+            Some(squash) => squash,
+            None => return bound
+        };
+        let amount = (SQUASH_AMOUNT * board.config.effects.intensity * left / SQUASH_TIME).min(0.9);
+        let (x_scale, y_scale) = if horizontal {
+            (1. + amount, 1. - amount)
+        } else {
+            (1. - amount, 1. + amount)
+        };
+        let (width, height) = (bound.width * x_scale, bound.height * y_scale);
+        game::Rect {
+            x: bound.x + (bound.width - width) / 2.,
+            y: bound.y + (bound.height - height) / 2.,
+            width,
+            height
+        }
+    }
+
+    pub fn draw<C: Canvas + ?Sized>(&self, canvas: &mut C, board: &game::Board, theme: &theme::Theme) {
+        let config = &board.config.effects;
+        if !config.enabled {
+            return;
+        }
+        let ball = &board.ball;
+        let speed = ball.dx.hypot(ball.dy) / board.config.ball_max_speed;
+        let length = ((config.trail_length as f32 * speed.min(1.)).round() as usize).min(self.trail.len().saturating_sub(1));
+        // Oldest first, so newer points are drawn on top. The newest point is
+        // the ball itself.
+        for i in (1..=length).rev() {
+            let (x, y) = self.trail[i];
+            let fade = 1. - i as f32 / (length + 1) as f32;
+            let radius = board.config.ball_radius * (0.3 + 0.7 * fade);
+            canvas.draw_shape(board, game::Rect {
+                x: x - radius,
+                y: y - radius,
+                width: 2. * radius,
+                height: 2. * radius
+            }, Shape::Circle, fade_color(theme.ball, 0.5 * fade));
+        }
+        for particle in self.particles.iter() {
+            let life = 1. - particle.age / particle.lifetime;
+            let size = particle.size * (0.5 + 0.5 * life);
+            let color = match particle.tint {
+                Tint::Ball => theme.ball,
+                Tint::Paddle(game::Side::Lhs) => theme.lhs_paddle,
+                Tint::Paddle(game::Side::Rhs) => theme.rhs_paddle,
+                Tint::Score(game::Side::Lhs) => theme.lhs_score,
+                Tint::Score(game::Side::Rhs) => theme.rhs_score
+            };
+            canvas.draw_shape(board, game::Rect {
+                x: particle.x - size / 2.,
+                y: particle.y - size / 2.,
+                width: size,
+                height: size
+            }, Shape::Circle, fade_color(color, life));
+        }
+    }
+}
+
+fn fade_color(color: theme::Color, alpha: f32) -> theme::Color {
+    [color[0], color[1], color[2], color[3] * alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+
+    const DT: f32 = 0.01;
+
+    /// Records what `Effects::draw` draws.
+    struct Shapes(Vec<theme::Color>);
+
+    impl Canvas for Shapes {
+        fn draw_shape(&mut self, _board: &game::Board, _rect: game::Rect, _shape: Shape, color: theme::Color) {
+            self.0.push(color);
+        }
+    }
+
+    fn drawn(effects: &Effects, board: &game::Board) -> Vec<theme::Color> {
+        let mut shapes = Shapes(Vec::new());
+        effects.draw(&mut shapes, board, &board.config.graphics.theme());
+        shapes.0
+    }
+
+    fn impact(x: f32, y: f32) -> game::Impact {
+        game::Impact { x, y, dx: 300., dy: 0. }
+    }
+
+    /// Updates for `seconds` of frames.
+    fn run(effects: &mut Effects, board: &game::Board, seconds: f32) {
+        for _ in 0..(seconds / DT).round() as u32 {
+            effects.update(board, DT);
+        }
+    }
+
+    #[test]
+    fn trail_follows_the_ball_and_shrinks_with_its_speed() {
+        let mut board = game::Board::new(GameConfig::default());
+        let trail_length = board.config.effects.trail_length as usize;
+        let mut effects = Effects::new();
+        board.ball.dx = board.config.ball_max_speed;
+        board.ball.dy = 0.;
+        for _ in 0..2 * trail_length {
+            board.ball.bound.x += board.ball.dx * DT;
+            effects.update(&board, DT);
+        }
+        assert_eq!(effects.trail.len(), trail_length + 1);
+        assert_eq!(drawn(&effects, &board).len(), trail_length);
+
+        // Half speed leaves half the trail, and a stopped ball none.
+        board.ball.dx /= 2.;
+        assert_eq!(drawn(&effects, &board).len(), trail_length / 2);
+        board.ball.dx = 0.;
+        assert!(drawn(&effects, &board).is_empty());
+
+        // A jump further than the ball can move starts a new trail.
+        board.ball.bound.x += 100.;
+        effects.update(&board, DT);
+        assert_eq!(effects.trail.len(), 1);
+    }
+
+    #[test]
+    fn sparks_fade_out_and_expire() {
+        let board = game::Board::new(GameConfig::default());
+        let mut effects = Effects::new();
+        effects.handle_event(&board, &game::Event::WallHit(impact(300., 5.)));
+        assert_eq!(effects.particles.len(), board.config.effects.spark_count as usize);
+        let alpha = |effects: &Effects| drawn(effects, &board).iter().map(|color| color[3]).fold(0., f32::max);
+
+        let mut last_alpha = alpha(&effects);
+        for _ in 0..10 {
+            run(&mut effects, &board, 0.02);
+            let faded = alpha(&effects);
+            assert!(faded < last_alpha);
+            last_alpha = faded;
+        }
+        run(&mut effects, &board, board.config.effects.particle_lifetime);
+        assert!(effects.particles.is_empty());
+    }
+
+    #[test]
+    fn goals_explode_and_clear_the_trail() {
+        let board = game::Board::new(GameConfig::default());
+        let mut effects = Effects::new();
+        effects.update(&board, DT);
+        effects.handle_event(&board, &game::Event::Goal(game::Side::Lhs, impact(600., 150.)));
+        assert!(effects.trail.is_empty());
+        assert_eq!(effects.particles.len(), board.config.effects.explosion_count as usize);
+        // Explosion particles get up to 1.5 times the usual lifetime, and a
+        // random factor of up to 1.5 on top.
+        run(&mut effects, &board, board.config.effects.particle_lifetime * EXPLOSION_LIFETIME * 1.5);
+        assert!(effects.particles.is_empty());
+    }
+
+    #[test]
+    fn disabled_effects_spawn_and_keep_nothing() {
+        let mut config = GameConfig::default();
+        config.effects.enabled = false;
+        let board = game::Board::new(config);
+        let mut effects = Effects::new();
+        effects.handle_event(&board, &game::Event::Goal(game::Side::Rhs, impact(0., 150.)));
+        effects.update(&board, DT);
+        assert!(effects.particles.is_empty());
+        assert!(effects.trail.is_empty());
+        assert!(drawn(&effects, &board).is_empty());
+    }
+
+    #[test]
+    fn shake_grows_with_trauma_and_settles() {
+        let board = game::Board::new(GameConfig::default());
+        let mut effects = Effects::new();
+        let slow = game::Impact { dx: board.config.ball_start_speed, ..impact(300., 5.) };
+        effects.handle_event(&board, &game::Event::WallHit(slow));
+        let hit_trauma = effects.trauma;
+        assert!(hit_trauma > 0. && hit_trauma < HIT_TRAUMA);
+        // Trauma tops out at 1.
+        for _ in 0..2 {
+            effects.handle_event(&board, &game::Event::Goal(game::Side::Lhs, impact(600., 150.)));
+        }
+        assert_eq!(effects.trauma, 1.);
+
+        effects.update(&board, DT);
+        let (x, y) = effects.shake();
+        assert!(x != 0. || y != 0.);
+        assert!(x.abs() <= SHAKE_DISTANCE && y.abs() <= SHAKE_DISTANCE);
+        run(&mut effects, &board, 1. / SHAKE_DECAY);
+        assert_eq!(effects.trauma, 0.);
+        assert_eq!(effects.shake(), (0., 0.));
+    }
+
+    #[test]
+    fn flash_lights_the_struck_paddle_and_fades() {
+        let board = game::Board::new(GameConfig::default());
+        let mut effects = Effects::new();
+        effects.handle_event(&board, &game::Event::PaddleHit(game::Side::Rhs, impact(590., 150.), 0.5));
+        assert_eq!(effects.flash(&board, game::Side::Rhs), 1.);
+        assert_eq!(effects.flash(&board, game::Side::Lhs), 0.);
+        effects.update(&board, FLASH_TIME / 2.);
+        assert!((effects.flash(&board, game::Side::Rhs) - 0.5).abs() < 1e-4);
+        run(&mut effects, &board, FLASH_TIME);
+        assert_eq!(effects.flash(&board, game::Side::Rhs), 0.);
+    }
+
+    #[test]
+    fn squash_flattens_the_ball_against_the_wall_and_springs_back() {
+        let board = game::Board::new(GameConfig::default());
+        let bound = board.ball.bound;
+        let mut effects = Effects::new();
+        effects.handle_event(&board, &game::Event::WallHit(impact(300., board.config.ball_radius)));
+        let squashed = effects.ball_rect(&board, bound);
+        assert!(squashed.width > bound.width && squashed.height < bound.height);
+        assert!((squashed.x + squashed.width / 2. - (bound.x + bound.width / 2.)).abs() < 1e-4);
+        assert!((squashed.y + squashed.height / 2. - (bound.y + bound.height / 2.)).abs() < 1e-4);
+
+        // Paddles are vertical, so a paddle hit squashes the other way.
+        effects.handle_event(&board, &game::Event::PaddleHit(game::Side::Lhs, impact(20., 150.), 0.5));
+        let squashed = effects.ball_rect(&board, bound);
+        assert!(squashed.width < bound.width && squashed.height > bound.height);
+
+        run(&mut effects, &board, SQUASH_TIME + DT);
+        let rect = effects.ball_rect(&board, bound);
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (bound.x, bound.y, bound.width, bound.height));
+    }
+
+    #[test]
+    fn zero_intensity_turns_the_juice_off() {
+        let mut config = GameConfig::default();
+        config.effects.intensity = 0.;
+        let board = game::Board::new(config);
+        let mut effects = Effects::new();
+        effects.handle_event(&board, &game::Event::PaddleHit(game::Side::Lhs, impact(20., 150.), 0.5));
+        effects.handle_event(&board, &game::Event::Goal(game::Side::Lhs, impact(600., 150.)));
+        effects.update(&board, DT);
+        assert_eq!(effects.shake(), (0., 0.));
+        assert_eq!(effects.flash(&board, game::Side::Lhs), 0.);
+        assert!(effects.squash.is_none());
+    }
+}
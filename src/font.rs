@@ -0,0 +1,192 @@
+use game::Rect;
+
+/// Glyph cell size in font pixels, not counting the one pixel gap between
+/// characters.
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// 5x7 bitmaps, top row first. Lowercase letters are drawn as uppercase and
+/// anything missing as a blank.
+const GLYPHS: &[(char, [&str; 7])] = &[
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["###..", "#..#.", "#...#", "#...#", "#...#", "#..#.", "###.."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+    (';', [".....", ".##..", ".##..", ".....", ".##..", "..#..", ".#..."]),
+    ('!', ["..#..", "..#..", "..#..", "..#..", "..#..", ".....", "..#.."]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    ('=', [".....", ".....", "#####", ".....", "#####", ".....", "....."]),
+    ('/', [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."]),
+    ('(', ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."]),
+    (')', [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."]),
+    ('\'', ["..#..", "..#..", ".#...", ".....", ".....", ".....", "....."]),
+    ('"', [".#.#.", ".#.#.", ".....", ".....", ".....", ".....", "....."]),
+    ('%', ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"]),
+    ('_', [".....", ".....", ".....", ".....", ".....", ".....", "#####"]),
+    ('<', ["...#.", "..#..", ".#...", "#....", ".#...", "..#..", "...#."]),
+    ('>', [".#...", "..#..", "...#.", "....#", "...#.", "..#..", ".#..."]),
+    ('#', [".#.#.", ".#.#.", "#####", ".#.#.", "#####", ".#.#.", ".#.#."]),
+    ('*', [".....", "..#..", "#.#.#", ".###.", "#.#.#", "..#..", "....."]),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    Small,
+    Medium,
+    Large
+}
+
+impl Size {
+    /// Side of one font pixel, in board units.
+    pub fn pixel(&self) -> f32 {
+        match *self {
+            Size::Small => 2.,
+            Size::Medium => 3.,
+            Size::Large => 6.
+        }
+    }
+
+    pub fn height(&self) -> f32 {
+        GLYPH_HEIGHT as f32 * self.pixel()
+    }
+}
+
+/// Which point of the text `x` refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right
+}
+
+fn glyph(c: char) -> Option<&'static [&'static str; 7]> {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().find(|&&(glyph_char, _)| glyph_char == c).map(|(_, rows)| rows)
+}
+
+pub fn text_width(text: &str, size: Size) -> f32 {
+    match text.chars().count() as u32 {
+        0 => 0.,
+        n => (n * (GLYPH_WIDTH + 1) - 1) as f32 * size.pixel()
+    }
+}
+
+/// Lays out a single line of text as rects, with the bottom of the glyphs at
+/// `y`. Runs of lit pixels within a row are merged into one rect.
+pub fn text_rects(text: &str, x: f32, y: f32, size: Size, align: Align) -> Vec<Rect> {
+    let pixel = size.pixel();
+    let left = match align {
+        Align::Left => x,
+        Align::Center => x - text_width(text, size) / 2.,
+        Align::Right => x - text_width(text, size)
+    };
+    let mut rects = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let rows = match glyph(c) {
+            Some(rows) => rows,
+            None => continue
+        };
+        let glyph_x = left + (i as u32 * (GLYPH_WIDTH + 1)) as f32 * pixel;
+        for (row, line) in rows.iter().enumerate() {
+            let row_y = y + (GLYPH_HEIGHT as usize - 1 - row) as f32 * pixel;
+            let mut run_start = None;
+            for (column, lit) in line.chars().map(|c| c == '#').chain(Some(false)).enumerate() {
+                match (lit, run_start) {
+                    (true, None) => run_start = Some(column),
+                    (false, Some(start)) => {
+                        rects.push(Rect {
+                            x: glyph_x + start as f32 * pixel,
+                            y: row_y,
+                            width: (column - start) as f32 * pixel,
+                            height: pixel
+                        });
+                        run_start = None;
+                    },
+                    _ => ()
+                }
+            }
+        }
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_counts_the_gaps_between_glyphs() {
+        assert_eq!(text_width("", Size::Small), 0.);
+        assert_eq!(text_width("A", Size::Small), 10.);
+        // Five pixels per glyph plus one between each pair.
+        assert_eq!(text_width("AB", Size::Small), 22.);
+        assert_eq!(text_width("AB", Size::Large), 66.);
+    }
+
+    #[test]
+    fn unknown_glyphs_are_blank_but_take_up_room() {
+        assert!(text_rects("~", 0., 0., Size::Small, Align::Left).is_empty());
+        assert_eq!(text_width("A~B", Size::Small), text_width("ABC", Size::Small));
+        let spaced = text_rects("I~I", 0., 0., Size::Small, Align::Left);
+        let plain = text_rects("II", 0., 0., Size::Small, Align::Left);
+        assert_eq!(spaced.len(), plain.len());
+        // The second I lands two glyph cells to the right.
+        assert_eq!(spaced[7].x, 2. + 2. * 6. * 2.);
+        // Lowercase is drawn as uppercase.
+        assert_eq!(text_rects("i", 0., 0., Size::Small, Align::Left).len(), 7);
+    }
+
+    #[test]
+    fn merges_runs_into_one_rect_each() {
+        // H has two runs on six rows and one on its bar; I has one per row.
+        let rects = text_rects("HI", 0., 0., Size::Small, Align::Left);
+        assert_eq!(rects.len(), 13 + 7);
+        let top_of_i = &rects[13];
+        assert_eq!((top_of_i.x, top_of_i.y, top_of_i.width, top_of_i.height), (14., 12., 6., 2.));
+    }
+
+    #[test]
+    fn aligns_around_x() {
+        let width = text_width("T", Size::Medium);
+        let left = text_rects("T", 100., 0., Size::Medium, Align::Left)[0].x;
+        assert_eq!(text_rects("T", 100., 0., Size::Medium, Align::Center)[0].x, left - width / 2.);
+        assert_eq!(text_rects("T", 100., 0., Size::Medium, Align::Right)[0].x, left - width);
+    }
+}
@@ -1,33 +1,249 @@
-extern crate glium;
-extern crate ears;
-use ears::{Sound, AudioController};
-
-const BOARD_WIDTH: f32 = 600.;
-const BOARD_HEIGHT: f32 = 300.;
-const PADDLE_X_OFFSET: f32 = 10.;
-const PADDLE_WIDTH: f32 = 10.;
-const PADDLE_HEIGHT: f32 = 60.;
-const GOAL_HEIGHT: f32 = 240.;
-const BALL_RADIUS: f32 = 5.;
-const BALL_MAX_SPEED: f32 = 800.;
-const BALL_MAX_SLOPE: f32 = 1.;
-const BALL_SPEEDUP: f32 = 1.05;
-const BALL_START_SPEED: f32 = 300.;
-const PADDLE_MAX_SPEED: f32 = 500.;
-const PADDLE_FRICTION: f32 = 0.005;
-const PADDLE_BALL_INFLUENCE: f32 = 0.3;
-const PADDLE_CURVE: f32 = 0.5;
-const PLAYER_PADDLE_ACCEL: f32 = 2000.;
-const HIT_DELAY: f32 = 0.05;
-const AI_PADDLE_P_FACTOR: f32 = 40.;
-const AI_PADDLE_I_FACTOR: f32 = 0.1;
-const AI_PADDLE_D_FACTOR: f32 = 2.;
-const AI_PADDLE_MAX_ACCEL: f32 = 1800.;
-const BEEP_PATH: &'static str = "sounds/beep.wav";
-const TICK_PATH: &'static str = "sounds/tick.wav";
-const ERROR_PATH: &'static str = "sounds/error.wav";
-
-const WIN_SCORE: u32 = 10;
+use config::GameConfig;
+use hash::Fnv;
+use rng::Rng;
+
+/// Number of simulation steps per second.
+pub const TICK_RATE: u32 = 120;
+/// Fixed duration of a single simulation step, in seconds.
+pub const TICK_DT: f32 = 1. / TICK_RATE as f32;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+    Lhs,
+    Rhs
+}
+
+/// Where and how hard the ball hit something.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Impact {
+    /// Centre of the ball at the moment of contact.
+    pub x: f32,
+    pub y: f32,
+    /// Ball velocity leaving the contact.
+    pub dx: f32,
+    pub dy: f32
+}
+
+impl Impact {
+    pub fn speed(&self) -> f32 {
+        self.dx.hypot(self.dy)
+    }
+}
+
+/// Something that happened during `Board::update` that the audio and render
+/// layers may want to react to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The ball bounced off the paddle on the given side. The last value is
+    /// where along the paddle it hit, from 0 at the bottom to 1 at the top.
+    PaddleHit(Side, Impact, f32),
+    /// The ball bounced off a wall or goal border.
+    WallHit(Impact),
+    /// The given side scored a point. The impact is where the ball left the
+    /// board, before it was served again.
+    Goal(Side, Impact),
+    /// The given side collected a power-up. The impact is where the
+    /// power-up was.
+    PowerUp(Side, PowerUpKind, Impact)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Makes the collector's paddle taller.
+    Grow,
+    /// Makes the opponent's paddle shorter.
+    Shrink,
+    /// Speeds the ball up.
+    SpeedBurst,
+    /// Narrows the collector's goal, which the opponent aims at.
+    GoalShrink,
+    /// Walls off the collector's goal until it blocks the ball once.
+    Shield
+}
+
+const POWER_UP_KINDS: [PowerUpKind; 5] = [
+    PowerUpKind::Grow,
+    PowerUpKind::Shrink,
+    PowerUpKind::SpeedBurst,
+    PowerUpKind::GoalShrink,
+    PowerUpKind::Shield
+];
+
+impl PowerUpKind {
+    /// Drawn on the power-up and next to its timer.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PowerUpKind::Grow => "+",
+            PowerUpKind::Shrink => "-",
+            PowerUpKind::SpeedBurst => ">",
+            PowerUpKind::GoalShrink => "G",
+            PowerUpKind::Shield => "S"
+        }
+    }
+}
+
+/// A power-up waiting on the board, centred on `(x, y)`.
+#[derive(Copy, Clone, Debug)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub x: f32,
+    pub y: f32
+}
+
+/// A collected power-up that is still in effect.
+#[derive(Copy, Clone, Debug)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    /// Who collected it.
+    pub side: Side,
+    /// Seconds left.
+    pub remaining: f32
+}
+
+/// Steers a paddle automatically from a read-only view of the board.
+pub trait PaddleController {
+    /// Returns the acceleration for the paddle on `side` over the next `dt`.
+    fn accel(&mut self, board: &Board, side: Side, dt: f32) -> f32;
+
+    /// Forgets any state carried over from the previous rally.
+    fn reset(&mut self) {}
+
+    fn box_clone(&self) -> Box<dyn PaddleController>;
+}
+
+impl Clone for Box<dyn PaddleController> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Who steers a paddle.
+#[derive(Clone)]
+pub enum Controller {
+    /// Driven by `Input::Up`/`Input::Down`/`Input::Stick`/`Input::Aim` for
+    /// the paddle's side.
+    Human,
+    /// Driven by a `PaddleController` inside `Board::update`.
+    Ai(Box<dyn PaddleController>),
+    /// Nobody; the paddle stays put.
+    Idle
+}
+
+impl Controller {
+    pub fn is_human(&self) -> bool {
+        matches!(*self, Controller::Human)
+    }
+}
+
+/// Abstract player input, decoupled from any windowing library.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Up(Side),
+    Down(Side),
+    /// An analog stick from `-STICK_MAX` (down) to `STICK_MAX` (up), which
+    /// accelerates the paddle in proportion. Sent as pressed unless centred,
+    /// so a centred stick gets through menus like a released key.
+    Stick(Side, i16),
+    /// A board height, e.g. under the mouse, that the paddle centre heads
+    /// for at up to its top speed until a key or stick takes over.
+    Aim(Side, i16),
+    ToggleBallSim,
+    BallUp,
+    BallDown,
+    /// Re-serves without touching the score. Not bound by default any more,
+    /// but kept so older replays still play back.
+    Restart
+}
+
+impl Input {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Input::Up(Side::Lhs) => "lhs_up",
+            Input::Down(Side::Lhs) => "lhs_down",
+            Input::Up(Side::Rhs) => "rhs_up",
+            Input::Down(Side::Rhs) => "rhs_down",
+            Input::Stick(Side::Lhs, _) => "lhs_stick",
+            Input::Stick(Side::Rhs, _) => "rhs_stick",
+            Input::Aim(Side::Lhs, _) => "lhs_aim",
+            Input::Aim(Side::Rhs, _) => "rhs_aim",
+            Input::ToggleBallSim => "toggle_ball_sim",
+            Input::BallUp => "ball_up",
+            Input::BallDown => "ball_down",
+            Input::Restart => "restart"
+        }
+    }
+
+    /// Only covers inputs without a value, so not sticks or aims.
+    pub fn from_name(name: &str) -> Option<Input> {
+        match name {
+            "lhs_up" => Some(Input::Up(Side::Lhs)),
+            "lhs_down" => Some(Input::Down(Side::Lhs)),
+            "rhs_up" => Some(Input::Up(Side::Rhs)),
+            "rhs_down" => Some(Input::Down(Side::Rhs)),
+            "toggle_ball_sim" => Some(Input::ToggleBallSim),
+            "ball_up" => Some(Input::BallUp),
+            "ball_down" => Some(Input::BallDown),
+            "restart" => Some(Input::Restart),
+            _ => None
+        }
+    }
+
+    /// Compact encoding used on the wire by netplay. Stick positions and
+    /// aims aren't part of the code and are sent separately.
+    pub fn code(&self) -> u8 {
+        match *self {
+            Input::Stick(Side::Lhs, _) => STICK_CODE,
+            Input::Stick(Side::Rhs, _) => STICK_CODE + 1,
+            Input::Aim(Side::Lhs, _) => STICK_CODE + 2,
+            Input::Aim(Side::Rhs, _) => STICK_CODE + 3,
+            _ => INPUT_CODES.iter().position(|input| input == self).unwrap() as u8
+        }
+    }
+
+    /// Sticks come back centred and aims at 0.
+    pub fn from_code(code: u8) -> Option<Input> {
+        match code.checked_sub(STICK_CODE) {
+            Some(0) => Some(Input::Stick(Side::Lhs, 0)),
+            Some(1) => Some(Input::Stick(Side::Rhs, 0)),
+            Some(2) => Some(Input::Aim(Side::Lhs, 0)),
+            Some(3) => Some(Input::Aim(Side::Rhs, 0)),
+            _ => INPUT_CODES.get(code as usize).cloned()
+        }
+    }
+
+    /// The value carried by sticks and aims.
+    pub fn value(&self) -> Option<i16> {
+        match *self {
+            Input::Stick(_, value) | Input::Aim(_, value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// The same input with its value, for those that carry one. Also returns
+    /// whether it counts as pressed: a stick unless centred, an aim always.
+    pub fn with_value(&self, value: i16) -> (Input, bool) {
+        match *self {
+            Input::Stick(side, _) => (Input::Stick(side, value), value != 0),
+            Input::Aim(side, _) => (Input::Aim(side, value), true),
+            input => (input, value != 0)
+        }
+    }
+}
+
+/// Full deflection of an `Input::Stick`.
+pub const STICK_MAX: i16 = i16::MAX;
+const STICK_CODE: u8 = INPUT_CODES.len() as u8;
+
+const INPUT_CODES: [Input; 8] = [
+    Input::Up(Side::Lhs),
+    Input::Down(Side::Lhs),
+    Input::Up(Side::Rhs),
+    Input::Down(Side::Rhs),
+    Input::ToggleBallSim,
+    Input::BallUp,
+    Input::BallDown,
+    Input::Restart
+];
 
 #[derive(Copy, Clone)]
 pub struct Rect {
@@ -48,12 +264,18 @@ impl Rect {
     }
 }
 
+#[derive(Clone)]
 pub struct Paddle {
     pub bound: Rect,
     pub dy: f32,
-    pub ddy: f32
+    pub ddy: f32,
+    /// Height for the paddle centre set by `Input::Aim`, which overrides
+    /// the acceleration.
+    pub target: Option<f32>,
+    pub controller: Controller
 }
 
+#[derive(Clone)]
 pub struct Ball {
     pub bound: Rect,
     pub dx: f32,
@@ -61,20 +283,21 @@ pub struct Ball {
 }
 
 impl Ball {
-    pub fn set_speed(&mut self, dx: f32, dy: f32) {
+    pub fn set_speed(&mut self, dx: f32, dy: f32, config: &GameConfig) {
         let mut mag = (dx * dx + dy * dy).sqrt();
         let mut slope = dy / dx;
-        if mag > BALL_MAX_SPEED {
-            mag = BALL_MAX_SPEED;
+        if mag > config.ball_max_speed {
+            mag = config.ball_max_speed;
         }
-        if slope.abs() > BALL_MAX_SLOPE {
-            slope = slope.signum() * BALL_MAX_SLOPE;
+        if slope.abs() > config.ball_max_slope {
+            slope = slope.signum() * config.ball_max_slope;
         }
         self.dx = dx.signum() * mag / (1. + slope * slope).sqrt();
         self.dy = dx.signum() * mag * slope / (1. + slope * slope).sqrt();
     }
 }
 
+#[derive(Clone)]
 pub struct Board {
     pub lhs_score: u32,
     pub rhs_score: u32,
@@ -85,16 +308,28 @@ pub struct Board {
     pub lhs_paddle: Paddle,
     pub rhs_paddle: Paddle,
     pub ball: Ball,
+    pub power_ups: Vec<PowerUp>,
+    pub active_effects: Vec<ActiveEffect>,
+    pub config: GameConfig,
+    tick: u64,
     delay: f32,
+    /// `config.paddle_friction_per_tick`, worked out once.
+    paddle_friction: f32,
     override_ball_sim: bool,
-    ai_last_offset: f32,
-    ai_accum_offset: f32,
-    beep_snd: Sound,
-    tick_snd: Sound,
-    error_snd: Sound
+    serve_rng: Option<Rng>,
+    power_up_rng: Rng,
+    /// Seconds until the next power-up spawns.
+    power_up_timer: f32,
+    /// Who gets the power-ups the ball runs into.
+    last_hitter: Option<Side>,
+    events: Vec<Event>
 }
 
-fn collides(sx: f32, sy: f32, sdx: f32, sdy: f32, tx: f32, ty: f32, tdx: f32, tdy: f32) -> (f32, f32) {
+/// Intersects the segment from `(sx, sy)` along `(sdx, sdy)` with the segment
+/// from `(tx, ty)` along `(tdx, tdy)`. Returns how far along each segment the
+/// crossing lies, so both values are in `(0, 1]` when the segments touch.
+#[allow(clippy::too_many_arguments)]
+pub fn collides(sx: f32, sy: f32, sdx: f32, sdy: f32, tx: f32, ty: f32, tdx: f32, tdy: f32) -> (f32, f32) {
     let (stdx, stdy) = (sx - tx, sy - ty);
     let cn = (tdx * stdx + tdy * stdy) / (tdx * tdx + tdy * tdy);
     let (nx, ny) = (tx + cn * tdx - sx, ty + cn * tdy - sy);
@@ -105,107 +340,183 @@ fn collides(sx: f32, sy: f32, sdx: f32, sdy: f32, tx: f32, ty: f32, tdx: f32, td
 }
 
 impl Board {
-    pub fn new() -> Self {
+    pub fn new(config: GameConfig) -> Self {
         Board {
             lhs_score: 0,
             rhs_score: 0,
-            width: BOARD_WIDTH,
-            height: BOARD_HEIGHT,
+            width: config.board_width,
+            height: config.board_height,
             lhs_paddle: Paddle {
                 bound: Rect {
-                    x: PADDLE_X_OFFSET - PADDLE_WIDTH / 2.,
-                    y: BOARD_HEIGHT / 2. - PADDLE_HEIGHT / 2.,
-                    width: PADDLE_WIDTH,
-                    height: PADDLE_HEIGHT
+                    x: config.paddle_x_offset - config.paddle_width / 2.,
+                    y: config.board_height / 2. - config.paddle_height / 2.,
+                    width: config.paddle_width,
+                    height: config.paddle_height
                 },
                 dy: 0.,
-                ddy: 0. 
+                ddy: 0.,
+                target: None,
+                controller: Controller::Human
             },
             rhs_paddle: Paddle {
                 bound: Rect {
-                    x: BOARD_WIDTH - PADDLE_X_OFFSET - PADDLE_WIDTH / 2.,
-                    y: BOARD_HEIGHT / 2. - PADDLE_HEIGHT / 2.,
-                    width: PADDLE_WIDTH,
-                    height: PADDLE_HEIGHT
+                    x: config.board_width - config.paddle_x_offset - config.paddle_width / 2.,
+                    y: config.board_height / 2. - config.paddle_height / 2.,
+                    width: config.paddle_width,
+                    height: config.paddle_height
                 },
                 dy: 0.,
-                ddy: 0. 
+                ddy: 0.,
+                target: None,
+                controller: Controller::Idle
             },
-            lhs_goal_height: GOAL_HEIGHT,
-            rhs_goal_height: GOAL_HEIGHT,
+            lhs_goal_height: config.goal_height,
+            rhs_goal_height: config.goal_height,
             ball: Ball {
                 bound: Rect {
-                    x: BOARD_WIDTH / 2. - BALL_RADIUS,
-                    y: BOARD_HEIGHT / 2. - BALL_RADIUS,
-                    width: 2. * BALL_RADIUS,
-                    height: 2. * BALL_RADIUS
+                    x: config.board_width / 2. - config.ball_radius,
+                    y: config.board_height / 2. - config.ball_radius,
+                    width: 2. * config.ball_radius,
+                    height: 2. * config.ball_radius
                 },
                 dx: 0.,
                 dy: 0.
             },
+            power_ups: Vec::new(),
+            active_effects: Vec::new(),
+            power_up_timer: config.power_ups.spawn_interval,
+            paddle_friction: config.paddle_friction_per_tick(),
+            config,
+            tick: 0,
             delay: 0.,
             override_ball_sim: false,
-            ai_last_offset: 0.,
-            ai_accum_offset: 0.,
-            beep_snd: Sound::new(BEEP_PATH).expect("Failed to load beep sound."),
-            tick_snd: Sound::new(TICK_PATH).expect("Failed to load tick sound."),
-            error_snd: Sound::new(ERROR_PATH).expect("Failed to load error sound.")
+            serve_rng: None,
+            power_up_rng: Rng::new(0),
+            last_hitter: None,
+            events: Vec::new()
         }
     }
 
+    /// Number of times `update` has been called.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Hashes the simulation state, so two instances fed the same inputs can
+    /// check that they still agree.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv::new();
+        let mut feed = |word: u64| hash.write_u64(word);
+        feed(self.tick);
+        feed(u64::from(self.lhs_score));
+        feed(u64::from(self.rhs_score));
+        feed(u64::from(self.delay.to_bits()));
+        feed(self.override_ball_sim as u64);
+        for paddle in [&self.lhs_paddle, &self.rhs_paddle].iter() {
+            for value in [paddle.bound.x, paddle.bound.y, paddle.dy, paddle.ddy].iter() {
+                feed(u64::from(value.to_bits()));
+            }
+            feed(paddle.target.map_or(u64::MAX, |target| u64::from(target.to_bits())));
+        }
+        for value in [self.ball.bound.x, self.ball.bound.y, self.ball.dx, self.ball.dy].iter() {
+            feed(u64::from(value.to_bits()));
+        }
+        feed(u64::from(self.power_up_timer.to_bits()));
+        feed(self.last_hitter.map_or(0, |side| side as u64 + 1));
+        for power_up in self.power_ups.iter() {
+            feed(power_up.kind as u64);
+            feed(u64::from(power_up.x.to_bits()));
+            feed(u64::from(power_up.y.to_bits()));
+        }
+        for effect in self.active_effects.iter() {
+            feed(effect.kind as u64);
+            feed(effect.side as u64);
+            feed(u64::from(effect.remaining.to_bits()));
+        }
+        hash.finish()
+    }
+
+    /// Blends positions between `self` and the following state `next` for
+    /// rendering, where `alpha` is in `[0, 1]`. Falls back to `next` when a
+    /// point was scored in between, since the pieces teleported.
+    pub fn lerp(&self, next: &Board, alpha: f32) -> Board {
+        let mut board = next.clone();
+        if self.lhs_score != next.lhs_score || self.rhs_score != next.rhs_score {
+            return board;
+        }
+        fn lerp_rect(a: Rect, b: Rect, alpha: f32) -> Rect {
+            Rect {
+                x: a.x + (b.x - a.x) * alpha,
+                y: a.y + (b.y - a.y) * alpha,
+                width: b.width,
+                height: b.height
+            }
+        }
+        board.lhs_paddle.bound = lerp_rect(self.lhs_paddle.bound, next.lhs_paddle.bound, alpha);
+        board.rhs_paddle.bound = lerp_rect(self.rhs_paddle.bound, next.rhs_paddle.bound, alpha);
+        board.ball.bound = lerp_rect(self.ball.bound, next.ball.bound, alpha);
+        board
+    }
+
     pub fn winner(&self) -> Option<bool> {
-        if self.lhs_score == WIN_SCORE { Some(true) }
-        else if self.rhs_score == WIN_SCORE { Some(false) }
+        if self.lhs_score == self.config.win_score { Some(true) }
+        else if self.rhs_score == self.config.win_score { Some(false) }
         else { None }
     }
 
+    /// Takes the events emitted by `update` since the last call.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        ::std::mem::take(&mut self.events)
+    }
+
     pub fn update(&mut self, mut dt: f32) {
+        self.tick += 1;
         if self.delay >= dt {
             self.delay -= dt;
             return;
         }
         dt -= self.delay;
         self.delay = 0.;
+        self.update_power_ups(dt);
         // ai sim
-        let target = if self.ball.dx > 0. {
-            self.ball.bound.y + self.ball.bound.height / 2.
-        } else {
-            self.height / 2.
-        };
-        let target_offset = target - (self.rhs_paddle.bound.y + self.rhs_paddle.bound.height / 2.);
-        if self.ai_accum_offset.signum() != target_offset.signum() {
-            self.ai_accum_offset = 0.;
-        } else {
-            self.ai_accum_offset += target_offset;
-        }
-        let p = target_offset;
-        let i = self.ai_accum_offset;
-        let d = (p - self.ai_last_offset) / dt;
-        self.ai_last_offset = target_offset;
-        let ddy_diff = AI_PADDLE_P_FACTOR * p + AI_PADDLE_I_FACTOR * i + AI_PADDLE_D_FACTOR * d - self.rhs_paddle.ddy;
-        if ddy_diff.abs() > AI_PADDLE_MAX_ACCEL {
-            self.rhs_paddle.ddy += ddy_diff.signum() * AI_PADDLE_MAX_ACCEL;
-        } else {
-            self.rhs_paddle.ddy += ddy_diff;
+        for &side in [Side::Lhs, Side::Rhs].iter() {
+            let mut controller = ::std::mem::replace(&mut self.paddle_mut(side).controller, Controller::Idle);
+            if let Controller::Ai(ref mut ai) = controller {
+                self.paddle_mut(side).ddy = ai.accel(self, side, dt);
+            }
+            self.paddle_mut(side).controller = controller;
         }
         // paddle sim
         for ref mut paddle in [&mut self.lhs_paddle, &mut self.rhs_paddle].iter_mut() {
-            paddle.dy += dt * paddle.ddy;
-            if paddle.dy.abs() > PADDLE_MAX_SPEED {
-                paddle.dy = paddle.dy.signum() * PADDLE_MAX_SPEED;
+            let max_speed = self.config.paddle_max_speed;
+            if let Some(target) = paddle.target {
+                // As fast as allowed, without overshooting.
+                let centre = paddle.bound.y + paddle.bound.height / 2.;
+                paddle.dy = ((target - centre) / dt).clamp(-max_speed, max_speed);
+            } else {
+                paddle.dy += dt * paddle.ddy;
+                if paddle.dy.abs() > max_speed {
+                    paddle.dy = paddle.dy.signum() * max_speed;
+                }
+                // Once per tick, including the shorter one a hit delay ends on.
+                paddle.dy *= self.paddle_friction;
             }
-            paddle.dy *= PADDLE_FRICTION.powf(dt);
             let mut y = paddle.bound.y + paddle.dy * dt;
             if y < 0. {
                 y = 0.;
                 paddle.dy = paddle.dy.abs();
-            } else if y + paddle.bound.height > BOARD_HEIGHT {
-                y = BOARD_HEIGHT - paddle.bound.height;
+            } else if y + paddle.bound.height > self.height {
+                y = self.height - paddle.bound.height;
                 paddle.dy = -paddle.dy.abs();
             }
             paddle.bound.y = y;
         }
         const MAX_ITERATIONS: u32 = 10;
+        // A ball stopped on a surface at the end of a tick can be rounded to
+        // just behind it; it still bounces off on the next tick instead of
+        // slipping through. Only surfaces the ball heads into are checked,
+        // so the one it just left is never hit twice.
+        const CONTACT_SLOP: f32 = 1e-4;
         let mut iterations = 0;
         let mut has_collide = true;
         let mut dt_left = dt;
@@ -213,157 +524,648 @@ impl Board {
         let rhs_goal_border_height = (self.height - self.rhs_goal_height) / 2.;
         enum Normal<'a> {
             Static(f32, f32),
-            Dynamic(f32, f32, &'a Fn(f32, &Ball) -> (f32, f32))
+            Dynamic(f32, f32, &'a dyn Fn(f32, &Ball) -> (f32, f32))
         }
         fn reflect(vx: f32, vy: f32, nx: f32, ny: f32) -> (f32, f32) {
             let dot = -2. * (nx * vx + ny * vy) / (nx * nx + ny * ny);
             (vx + dot * nx, vy + dot * ny)
         }
-        fn paddle_reflect(nx: f32, paddle_dy: f32, ct: f32, ball: &Ball) -> (f32, f32) {
-           let ny = (2. * ct - 1.) * PADDLE_CURVE;
-           let (dx, dy) = reflect(ball.dx.signum() * (ball.dx * ball.dx + ball.dy * ball.dy).sqrt(), paddle_dy * PADDLE_BALL_INFLUENCE, nx, ny);
-           (BALL_SPEEDUP * dx, BALL_SPEEDUP * dy)
+        fn paddle_reflect(nx: f32, paddle_dy: f32, ct: f32, ball: &Ball, config: &GameConfig) -> (f32, f32) {
+           let ny = (2. * ct - 1.) * config.paddle_curve;
+           let (dx, dy) = reflect(ball.dx.signum() * (ball.dx * ball.dx + ball.dy * ball.dy).sqrt(), paddle_dy * config.paddle_ball_influence, nx, ny);
+           (config.ball_speedup * dx, config.ball_speedup * dy)
         }
+        let config = &self.config;
+        let ball_radius = config.ball_radius;
         let lhs_paddle_dy = self.lhs_paddle.dy;
         let rhs_paddle_dy = self.rhs_paddle.dy;
-        let lhs_reflect_fn = |ct: f32, ball: &Ball| { paddle_reflect(1., lhs_paddle_dy, ct, ball) };
-        let rhs_reflect_fn = |ct: f32, ball: &Ball| { paddle_reflect(-1., rhs_paddle_dy, ct, ball) };
-        let mut hit_paddle_lhs = false;
-        let mut hit_paddle_rhs = false;
-        let mut hit_any = false;
+        let lhs_reflect_fn = |ct: f32, ball: &Ball| { paddle_reflect(1., lhs_paddle_dy, ct, ball, config) };
+        let rhs_reflect_fn = |ct: f32, ball: &Ball| { paddle_reflect(-1., rhs_paddle_dy, ct, ball, config) };
+        let speed_burst = self.speed_burst();
+        let mut hit_paddle = false;
         {
-            let mut lhs_callback_fn = || { hit_paddle_lhs = true; };
-            let mut rhs_callback_fn = || { hit_paddle_rhs = true; };
-            let mut colliders: [(f32, f32, f32, f32, Normal, Option<&mut FnMut()>, bool); 8] = [
-                (0., BALL_RADIUS, self.width, 0., Normal::Static(0., 1.), None, false),
-                (0., self.height - BALL_RADIUS, self.width, 0., Normal::Static(0., -1.), None, false),
-                (BALL_RADIUS, 0., 0., lhs_goal_border_height, Normal::Static(1., 0.), None, false),
-                (BALL_RADIUS, self.height, 0., -lhs_goal_border_height, Normal::Static(1., 0.), None, false),
-                (self.width - BALL_RADIUS, 0., 0., lhs_goal_border_height, Normal::Static(-1., 0.), None, false),
-                (self.width - BALL_RADIUS, self.height, 0., -rhs_goal_border_height, Normal::Static(-1., 0.), None, false),
-                (self.lhs_paddle.bound.x + self.lhs_paddle.bound.width + BALL_RADIUS, self.lhs_paddle.bound.y - BALL_RADIUS, 0., self.lhs_paddle.bound.height + 2. * BALL_RADIUS, 
-                    Normal::Dynamic(1., 0., &lhs_reflect_fn), Some(&mut lhs_callback_fn), true),
-                (self.rhs_paddle.bound.x - BALL_RADIUS, self.rhs_paddle.bound.y - BALL_RADIUS, 0., self.rhs_paddle.bound.height + 2. * BALL_RADIUS, 
-                    Normal::Dynamic(-1., 0., &rhs_reflect_fn), Some(&mut rhs_callback_fn), true),
+            #[derive(Copy, Clone)]
+            enum Surface {
+                Wall,
+                /// Paddles also end the iterations early.
+                Paddle(Side),
+                /// Goes away after blocking the ball once.
+                Shield(Side)
+            }
+            let mut colliders: Vec<(f32, f32, f32, f32, Normal, Surface)> = vec![
+                (0., ball_radius, self.width, 0., Normal::Static(0., 1.), Surface::Wall),
+                (0., self.height - ball_radius, self.width, 0., Normal::Static(0., -1.), Surface::Wall),
+                (ball_radius, 0., 0., lhs_goal_border_height, Normal::Static(1., 0.), Surface::Wall),
+                (ball_radius, self.height, 0., -lhs_goal_border_height, Normal::Static(1., 0.), Surface::Wall),
+                (self.width - ball_radius, 0., 0., rhs_goal_border_height, Normal::Static(-1., 0.), Surface::Wall),
+                (self.width - ball_radius, self.height, 0., -rhs_goal_border_height, Normal::Static(-1., 0.), Surface::Wall),
+                (self.lhs_paddle.bound.x + self.lhs_paddle.bound.width + ball_radius, self.lhs_paddle.bound.y - ball_radius, 0., self.lhs_paddle.bound.height + 2. * ball_radius, 
+                    Normal::Dynamic(1., 0., &lhs_reflect_fn), Surface::Paddle(Side::Lhs)),
+                (self.rhs_paddle.bound.x - ball_radius, self.rhs_paddle.bound.y - ball_radius, 0., self.rhs_paddle.bound.height + 2. * ball_radius, 
+                    Normal::Dynamic(-1., 0., &rhs_reflect_fn), Surface::Paddle(Side::Rhs)),
             ];
+            if self.has_shield(Side::Lhs) {
+                colliders.push((ball_radius, lhs_goal_border_height, 0., self.lhs_goal_height,
+                                Normal::Static(1., 0.), Surface::Shield(Side::Lhs)));
+            }
+            if self.has_shield(Side::Rhs) {
+                colliders.push((self.width - ball_radius, rhs_goal_border_height, 0., self.rhs_goal_height,
+                                Normal::Static(-1., 0.), Surface::Shield(Side::Rhs)));
+            }
             let mut kill_early = false;
             while dt_left > 0. && has_collide && iterations < MAX_ITERATIONS && !kill_early {
                 iterations += 1;
                 has_collide = false;
-                let iter_dx = self.ball.dx * dt_left;
-                let iter_dy = self.ball.dy * dt_left;
-                for &mut (tx, ty, tdx, tdy, ref normal, ref mut callback, early) in colliders.iter_mut() {
-                    let (nx, ny) = match normal {
-                        &Normal::Static(x, y) => (x, y),
-                        &Normal::Dynamic(x, y, _) => (x, y)
+                let iter_dx = self.ball.dx * speed_burst * dt_left;
+                let iter_dy = self.ball.dy * speed_burst * dt_left;
+                for &mut (tx, ty, tdx, tdy, ref normal, surface) in colliders.iter_mut() {
+                    let (nx, ny) = match *normal {
+                        Normal::Static(x, y) => (x, y),
+                        Normal::Dynamic(x, y, _) => (x, y)
                     };
                     if nx * self.ball.dx + ny * self.ball.dy >= 0. { continue; }
                     let (cs, ct) = collides(
-                        self.ball.bound.x + BALL_RADIUS, self.ball.bound.y + BALL_RADIUS, iter_dx, iter_dy,
+                        self.ball.bound.x + ball_radius, self.ball.bound.y + ball_radius, iter_dx, iter_dy,
                         tx, ty, tdx, tdy);
-                    if 0. < cs && cs <= 1. && 0. < ct && ct <= 1. {
+                    if -CONTACT_SLOP < cs && cs <= 1. && 0. < ct && ct <= 1. {
                         self.ball.bound.x += iter_dx * cs;
                         self.ball.bound.y += iter_dy * cs;
                         has_collide = true;
-                        hit_any = true;
-                        if early { kill_early = true; }
                         let (dx, dy) = if let &Normal::Dynamic(_, _, reflect_fn) = normal {
                             reflect_fn(ct, &self.ball)
                         } else {
                             reflect(self.ball.dx, self.ball.dy, nx, ny)
                         };
-                        self.ball.set_speed(dx, dy);
+                        self.ball.set_speed(dx, dy, config);
                         dt_left *= 1. - cs;
-                        if let &mut Some(ref mut callback_fn) = callback {
-                            callback_fn();
-                        }
+                        let impact = Impact {
+                            x: self.ball.bound.x + ball_radius,
+                            y: self.ball.bound.y + ball_radius,
+                            dx: self.ball.dx,
+                            dy: self.ball.dy
+                        };
+                        self.events.push(match surface {
+                            Surface::Paddle(side) => {
+                                hit_paddle = true;
+                                kill_early = true;
+                                self.last_hitter = Some(side);
+                                Event::PaddleHit(side, impact, ct)
+                            },
+                            Surface::Shield(side) => {
+                                self.active_effects.retain(|effect| {
+                                    !(effect.kind == PowerUpKind::Shield && effect.side == side)
+                                });
+                                Event::WallHit(impact)
+                            },
+                            Surface::Wall => Event::WallHit(impact)
+                        });
                         break;
                     }
                 }
             }
         }
         if !self.override_ball_sim {
-            if hit_paddle_lhs || hit_paddle_rhs {
-                self.delay = HIT_DELAY;
+            if hit_paddle {
+                self.delay = config.hit_delay;
             }
             if dt_left < self.delay {
-                self.delay = HIT_DELAY - dt_left;
+                self.delay = config.hit_delay - dt_left;
             } else {
-                self.ball.bound.x += self.ball.dx * (dt_left - self.delay);
-                self.ball.bound.y += self.ball.dy * (dt_left - self.delay);
+                self.ball.bound.x += self.ball.dx * speed_burst * (dt_left - self.delay);
+                self.ball.bound.y += self.ball.dy * speed_burst * (dt_left - self.delay);
                 self.delay = 0.;
             }
         }
-        if hit_paddle_lhs || hit_paddle_rhs {
-            self.beep_snd.play();
-        } else if hit_any {
-            self.tick_snd.play();
-        }
-        if self.ball.bound.x < 0. {
+        let exit = Impact {
+            x: self.ball.bound.x + config.ball_radius,
+            y: self.ball.bound.y + config.ball_radius,
+            dx: self.ball.dx,
+            dy: self.ball.dy
+        };
+        self.collect_power_ups();
+        // A ball bounced off the goal line at the very end of the tick can
+        // still be a hair past it; only one heading out scores.
+        if self.ball.bound.x < 0. && self.ball.dx < 0. {
             self.rhs_score += 1;
-            self.error_snd.play();
+            self.events.push(Event::Goal(Side::Rhs, exit));
             self.start_game(true);
-        } else if self.ball.bound.x > self.width {
+        } else if self.ball.bound.x > self.width && self.ball.dx > 0. {
             self.lhs_score += 1;
-            self.error_snd.play();
+            self.events.push(Event::Goal(Side::Lhs, exit));
             self.start_game(false);
         }
     }
 
     pub fn start_game(&mut self, lhs_start: bool) {
-        self.lhs_paddle.bound.x = PADDLE_X_OFFSET - PADDLE_WIDTH / 2.;
-        self.lhs_paddle.bound.y = BOARD_HEIGHT / 2. - PADDLE_HEIGHT / 2.;
+        let config = &self.config;
+        self.lhs_paddle.bound.x = config.paddle_x_offset - config.paddle_width / 2.;
+        self.lhs_paddle.bound.y = config.board_height / 2. - self.lhs_paddle.bound.height / 2.;
         self.lhs_paddle.dy = 0.;
         self.lhs_paddle.ddy = 0.;
-        self.rhs_paddle.bound.x = BOARD_WIDTH - PADDLE_X_OFFSET - PADDLE_WIDTH / 2.;
-        self.rhs_paddle.bound.y = BOARD_HEIGHT / 2. - PADDLE_HEIGHT / 2.;
+        self.rhs_paddle.bound.x = config.board_width - config.paddle_x_offset - config.paddle_width / 2.;
+        self.rhs_paddle.bound.y = config.board_height / 2. - self.rhs_paddle.bound.height / 2.;
         self.rhs_paddle.dy = 0.;
         self.rhs_paddle.ddy = 0.;
         self.delay = 0.;
-        self.ball.bound.x = BOARD_WIDTH / 2. - BALL_RADIUS;
-        self.ball.bound.y = BOARD_HEIGHT / 2. - BALL_RADIUS;
-        self.ball.dx = if lhs_start { -BALL_START_SPEED } else { BALL_START_SPEED };
-        self.ball.dy = 0.;
-        self.ai_last_offset = 0.;
-        self.ai_accum_offset = 0.;
+        self.last_hitter = None;
+        self.ball.bound.x = config.board_width / 2. - config.ball_radius;
+        self.ball.bound.y = config.board_height / 2. - config.ball_radius;
+        self.ball.dx = if lhs_start { -config.ball_start_speed } else { config.ball_start_speed };
+        self.ball.dy = match self.serve_rng {
+            Some(ref mut rng) => {
+                let slope = (2. * rng.next_f32() - 1.) * config.ball_max_slope / 2.;
+                slope * config.ball_start_speed
+            },
+            None => 0.
+        };
+        for paddle in [&mut self.lhs_paddle, &mut self.rhs_paddle].iter_mut() {
+            if let Controller::Ai(ref mut ai) = paddle.controller {
+                ai.reset();
+            }
+        }
+    }
+
+    /// Resets the score and serves, for a rematch.
+    pub fn new_match(&mut self, lhs_start: bool) {
+        self.lhs_score = 0;
+        self.rhs_score = 0;
+        self.power_ups.clear();
+        self.active_effects.clear();
+        self.power_up_timer = self.config.power_ups.spawn_interval;
+        self.apply_effects();
+        self.start_game(lhs_start);
+    }
+
+    /// Serves at a random angle drawn from `seed`, instead of straight
+    /// across, and places power-ups from it too. The serve takes effect from
+    /// the next `start_game`.
+    pub fn set_serve_seed(&mut self, seed: u64) {
+        self.serve_rng = Some(Rng::new(seed));
+        self.power_up_rng = Rng::new(!seed);
     }
 
-    pub fn handle_input(&mut self, key: glium::glutin::VirtualKeyCode, is_pressed: bool) {
+    /// How much faster than its velocity the ball moves right now.
+    pub fn speed_burst(&self) -> f32 {
+        self.active_effects.iter()
+            .filter(|effect| effect.kind == PowerUpKind::SpeedBurst)
+            .fold(1., |burst, _| burst * self.config.power_ups.speed_burst)
+    }
+
+    pub fn has_shield(&self, side: Side) -> bool {
+        self.active_effects.iter().any(|effect| effect.kind == PowerUpKind::Shield && effect.side == side)
+    }
+
+    /// Runs down the effect timers and spawns power-ups when they're due.
+    fn update_power_ups(&mut self, dt: f32) {
+        let count = self.active_effects.len();
+        for effect in self.active_effects.iter_mut() {
+            effect.remaining -= dt;
+        }
+        self.active_effects.retain(|effect| effect.remaining > 0.);
+        if self.active_effects.len() != count {
+            self.apply_effects();
+        }
+        let config = &self.config.power_ups;
+        if !config.enabled {
+            return;
+        }
+        self.power_up_timer -= dt;
+        if self.power_up_timer > 0. {
+            return;
+        }
+        self.power_up_timer += config.spawn_interval;
+        if self.power_ups.len() >= config.max_active as usize {
+            return;
+        }
+        let rng = &mut self.power_up_rng;
+        let kind = POWER_UP_KINDS[(rng.next_u64() % POWER_UP_KINDS.len() as u64) as usize];
+        // Kept to the middle, so there's room to steer the ball into them.
+        let x = self.width * (0.25 + 0.5 * rng.next_f32());
+        let y = config.radius + (self.height - 2. * config.radius) * rng.next_f32();
+        self.power_ups.push(PowerUp { kind, x, y });
+    }
+
+    /// Hands the power-ups the ball is touching to whoever hit it last.
+    /// Before the first hit of a rally the ball passes through them.
+    fn collect_power_ups(&mut self) {
+        let side = match self.last_hitter {
+            Some(side) => side,
+            None => return
+        };
+        let reach = self.config.power_ups.radius + self.config.ball_radius;
+        let x = self.ball.bound.x + self.config.ball_radius;
+        let y = self.ball.bound.y + self.config.ball_radius;
+        let (collected, missed): (Vec<PowerUp>, Vec<PowerUp>) = self.power_ups.iter()
+            .partition(|power_up| (power_up.x - x).hypot(power_up.y - y) < reach);
+        if collected.is_empty() {
+            return;
+        }
+        self.power_ups = missed;
+        let duration = self.config.power_ups.duration;
+        for power_up in collected {
+            // Collecting a kind that is already in effect starts it over.
+            match self.active_effects.iter_mut().find(|effect| effect.kind == power_up.kind && effect.side == side) {
+                Some(effect) => effect.remaining = duration,
+                None => self.active_effects.push(ActiveEffect { kind: power_up.kind, side, remaining: duration })
+            }
+            self.events.push(Event::PowerUp(side, power_up.kind, Impact {
+                x: power_up.x,
+                y: power_up.y,
+                dx: self.ball.dx,
+                dy: self.ball.dy
+            }));
+        }
+        self.apply_effects();
+    }
+
+    /// Sizes the paddles and goals for the effects in play. Paddles keep
+    /// their centre as they grow or shrink.
+    fn apply_effects(&mut self) {
+        for &side in [Side::Lhs, Side::Rhs].iter() {
+            let config = &self.config;
+            let mut paddle_height = config.paddle_height;
+            let mut goal_height = config.goal_height;
+            for effect in self.active_effects.iter() {
+                match effect.kind {
+                    PowerUpKind::Grow if effect.side == side => paddle_height *= config.power_ups.paddle_grow,
+                    PowerUpKind::Shrink if effect.side != side => paddle_height *= config.power_ups.paddle_shrink,
+                    PowerUpKind::GoalShrink if effect.side == side => goal_height *= config.power_ups.goal_shrink,
+                    _ => ()
+                }
+            }
+            let board_height = self.height;
+            let paddle_height = paddle_height.min(board_height);
+            let goal_height = goal_height.min(board_height);
+            let paddle = self.paddle_mut(side);
+            let centre = paddle.bound.y + paddle.bound.height / 2.;
+            paddle.bound.height = paddle_height;
+            paddle.bound.y = (centre - paddle_height / 2.).clamp(0., board_height - paddle_height);
+            match side {
+                Side::Lhs => self.lhs_goal_height = goal_height,
+                Side::Rhs => self.rhs_goal_height = goal_height
+            }
+        }
+    }
+
+    pub fn set_controller(&mut self, side: Side, controller: Controller) {
+        let paddle = self.paddle_mut(side);
+        paddle.controller = controller;
+        paddle.ddy = 0.;
+    }
+
+    pub fn paddle(&self, side: Side) -> &Paddle {
+        match side {
+            Side::Lhs => &self.lhs_paddle,
+            Side::Rhs => &self.rhs_paddle
+        }
+    }
+
+    fn paddle_mut(&mut self, side: Side) -> &mut Paddle {
+        match side {
+            Side::Lhs => &mut self.lhs_paddle,
+            Side::Rhs => &mut self.rhs_paddle
+        }
+    }
+
+    pub fn handle_input(&mut self, input: Input, is_pressed: bool) {
         // player input
-        match (key, is_pressed) {
-            (glium::glutin::VirtualKeyCode::Up, true) => {
-                self.lhs_paddle.ddy = PLAYER_PADDLE_ACCEL;
+        match (input, is_pressed) {
+            (Input::Up(side), _) | (Input::Down(side), _) | (Input::Stick(side, _), _) | (Input::Aim(side, _), _)
+                if !self.paddle(side).controller.is_human() => (),
+            (Input::Up(side), true) => {
+                let accel = self.config.player_paddle_accel;
+                let paddle = self.paddle_mut(side);
+                paddle.ddy = accel;
+                paddle.target = None;
             },
-            (glium::glutin::VirtualKeyCode::Up, false) => {
-                if self.lhs_paddle.ddy > 0. {
-                    self.lhs_paddle.ddy = 0.;
+            (Input::Up(side), false) => {
+                let paddle = self.paddle_mut(side);
+                if paddle.ddy > 0. {
+                    paddle.ddy = 0.;
                 }
             },
-            (glium::glutin::VirtualKeyCode::Down, true) => {
-                self.lhs_paddle.ddy = -PLAYER_PADDLE_ACCEL;
+            (Input::Down(side), true) => {
+                let accel = self.config.player_paddle_accel;
+                let paddle = self.paddle_mut(side);
+                paddle.ddy = -accel;
+                paddle.target = None;
             },
-            (glium::glutin::VirtualKeyCode::Down, false) => {
-                if self.lhs_paddle.ddy < 0. {
-                    self.lhs_paddle.ddy = 0.;
+            (Input::Down(side), false) => {
+                let paddle = self.paddle_mut(side);
+                if paddle.ddy < 0. {
+                    paddle.ddy = 0.;
                 }
             },
-            (glium::glutin::VirtualKeyCode::B, true) => {
+            (Input::Stick(side, value), _) => {
+                let amount = (f32::from(value) / f32::from(STICK_MAX)).clamp(-1., 1.);
+                let accel = self.config.player_paddle_accel;
+                let paddle = self.paddle_mut(side);
+                paddle.ddy = amount * accel;
+                if value != 0 {
+                    paddle.target = None;
+                }
+            },
+            (Input::Aim(side, y), _) => {
+                let height = self.height;
+                let paddle = self.paddle_mut(side);
+                let half = paddle.bound.height / 2.;
+                paddle.ddy = 0.;
+                paddle.target = Some(f32::from(y).clamp(half, height - half));
+            },
+            (Input::ToggleBallSim, true) => {
                 self.override_ball_sim = !self.override_ball_sim;
             },
-            (glium::glutin::VirtualKeyCode::W, true) => {
-                if self.override_ball_sim {
-                    self.ball.bound.y += 100.;
-                }
+            (Input::BallUp, true) if self.override_ball_sim => {
+                self.ball.bound.y += 100.;
             },
-            (glium::glutin::VirtualKeyCode::S, true) => {
-                if self.override_ball_sim {
-                    self.ball.bound.y -= 100.;
-                }
+            (Input::BallDown, true) if self.override_ball_sim => {
+                self.ball.bound.y -= 100.;
             },
-            (glium::glutin::VirtualKeyCode::Return, true) => {
+            (Input::Restart, true) => {
                 self.start_game(true);
             },
             _ => ()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn served_board() -> Board {
+        let mut board = Board::new(GameConfig::default());
+        board.start_game(true);
+        board
+    }
+
+    /// Steps `board` for up to `ticks` ticks and returns the first event
+    /// matching `want`.
+    fn run_until<F: Fn(&Event) -> bool>(board: &mut Board, ticks: u32, want: F) -> Option<Event> {
+        for _ in 0..ticks {
+            board.update(TICK_DT);
+            if let Some(event) = board.drain_events().into_iter().find(|event| want(event)) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn straight_serve_hits_the_paddle() {
+        let mut board = served_board();
+        let event = run_until(&mut board, 5 * TICK_RATE, |_| true);
+        match event {
+            Some(Event::PaddleHit(Side::Lhs, impact, ct)) => {
+                assert!(impact.dx > 0.);
+                assert!((ct - 0.5).abs() < 0.01);
+            },
+            other => panic!("expected a left paddle hit, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn steep_serve_hits_the_wall() {
+        let mut board = served_board();
+        board.ball.dy = board.config.ball_start_speed;
+        match run_until(&mut board, 5 * TICK_RATE, |_| true) {
+            Some(Event::WallHit(impact)) => {
+                assert!(impact.dy < 0.);
+                assert!((impact.y - (board.height - board.config.ball_radius)).abs() < 0.01);
+            },
+            other => panic!("expected a wall hit, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn missed_ball_is_a_goal() {
+        let mut board = served_board();
+        board.lhs_paddle.bound.y = 0.;
+        match run_until(&mut board, 5 * TICK_RATE, |event| matches!(*event, Event::Goal(..))) {
+            Some(Event::Goal(Side::Rhs, impact)) => assert!(impact.x < board.config.ball_radius),
+            other => panic!("expected a goal for the right side, got {:?}", other)
+        }
+        assert_eq!((board.lhs_score, board.rhs_score), (0, 1));
+        // Served again from the middle, towards the side that conceded.
+        assert!(board.ball.dx < 0.);
+        assert_eq!(board.ball.bound.x, board.width / 2. - board.config.ball_radius);
+    }
+
+    /// A board with a human on the left, fed `script`, against the hard AI.
+    fn scripted_board(seed: u64) -> Board {
+        let mut board = Board::new(GameConfig::default());
+        board.set_controller(Side::Lhs, Controller::Human);
+        board.set_controller(Side::Rhs, ::ai::ControllerKind::Ai(::ai::Difficulty::Hard).controller());
+        board.set_serve_seed(seed);
+        board.start_game(true);
+        board
+    }
+
+    fn script(tick: u64) -> Option<(Input, bool)> {
+        match tick % 90 {
+            0 => Some((Input::Up(Side::Lhs), true)),
+            20 => Some((Input::Up(Side::Lhs), false)),
+            45 => Some((Input::Down(Side::Lhs), true)),
+            70 => Some((Input::Down(Side::Lhs), false)),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_identical_states() {
+        let mut boards = [scripted_board(7), scripted_board(7)];
+        for _ in 0..60 * TICK_RATE {
+            for board in boards.iter_mut() {
+                if let Some((input, is_pressed)) = script(board.tick()) {
+                    board.handle_input(input, is_pressed);
+                }
+                board.update(TICK_DT);
+            }
+            assert_eq!(boards[0].checksum(), boards[1].checksum(), "diverged on tick {}", boards[0].tick());
+        }
+        assert!(boards[0].lhs_score + boards[0].rhs_score > 0);
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        let mut boards = [scripted_board(7), scripted_board(8)];
+        for board in boards.iter_mut() {
+            for _ in 0..TICK_RATE {
+                board.update(TICK_DT);
+            }
+        }
+        assert_ne!(boards[0].checksum(), boards[1].checksum());
+    }
+
+    #[test]
+    fn lerp_blends_the_ball_between_ticks() {
+        let previous = served_board();
+        let mut next = previous.clone();
+        next.update(TICK_DT);
+        let halfway = previous.lerp(&next, 0.5);
+        assert_eq!(halfway.ball.bound.x, (previous.ball.bound.x + next.ball.bound.x) / 2.);
+        assert_eq!(previous.lerp(&next, 1.).ball.bound.x, next.ball.bound.x);
+        assert_eq!(halfway.tick(), next.tick());
+    }
+
+    #[test]
+    fn each_human_paddle_follows_its_own_keys() {
+        let mut board = served_board();
+        board.set_controller(Side::Rhs, Controller::Human);
+        board.handle_input(Input::Up(Side::Lhs), true);
+        board.handle_input(Input::Down(Side::Rhs), true);
+        assert!(board.lhs_paddle.ddy > 0.);
+        assert!(board.rhs_paddle.ddy < 0.);
+        board.handle_input(Input::Up(Side::Lhs), false);
+        assert_eq!(board.lhs_paddle.ddy, 0.);
+        assert!(board.rhs_paddle.ddy < 0.);
+    }
+
+    #[test]
+    fn idle_paddles_ignore_input_and_stay_put() {
+        let mut board = served_board();
+        board.set_controller(Side::Lhs, Controller::Idle);
+        let y = board.lhs_paddle.bound.y;
+        board.handle_input(Input::Up(Side::Lhs), true);
+        board.handle_input(Input::Aim(Side::Lhs, 0), true);
+        for _ in 0..TICK_RATE {
+            board.update(TICK_DT);
+        }
+        assert_eq!(board.lhs_paddle.bound.y, y);
+        assert_eq!(board.lhs_paddle.target, None);
+    }
+
+    #[test]
+    fn events_are_drained_once() {
+        let mut board = served_board();
+        assert!(run_until(&mut board, 5 * TICK_RATE, |_| true).is_some());
+        assert!(board.drain_events().is_empty());
+    }
+
+    /// A served board where the left player has just run the ball into a
+    /// power-up of `kind`.
+    fn collected(kind: PowerUpKind) -> Board {
+        let mut config = GameConfig::default();
+        config.power_ups.enabled = true;
+        // Nothing spawns on its own during a test.
+        config.power_ups.spawn_interval = 1000.;
+        let mut board = Board::new(config);
+        board.start_game(true);
+        board.last_hitter = Some(Side::Lhs);
+        let radius = board.config.ball_radius;
+        let (x, y) = (board.ball.bound.x + radius, board.ball.bound.y + radius);
+        board.power_ups.push(PowerUp { kind, x, y });
+        board.update(TICK_DT);
+        let events = board.drain_events();
+        assert!(events.iter().any(|event| matches!(*event, Event::PowerUp(Side::Lhs, k, _) if k == kind)),
+                "{:?} wasn't collected: {:?}", kind, events);
+        assert!(board.power_ups.is_empty());
+        board
+    }
+
+    /// Holds the ball still and runs the clock past the power-up duration.
+    fn run_out(board: &mut Board) {
+        board.ball.dx = 0.;
+        board.ball.dy = 0.;
+        let ticks = (board.config.power_ups.duration / TICK_DT) as u32 + 2;
+        for _ in 0..ticks {
+            board.update(TICK_DT);
+        }
+        assert!(board.active_effects.is_empty());
+    }
+
+    #[test]
+    fn power_ups_wait_for_the_first_hit() {
+        let mut board = collected(PowerUpKind::Grow);
+        board.last_hitter = None;
+        let radius = board.config.ball_radius;
+        let (x, y) = (board.ball.bound.x + radius, board.ball.bound.y + radius);
+        board.power_ups.push(PowerUp { kind: PowerUpKind::Shrink, x, y });
+        board.update(TICK_DT);
+        assert_eq!(board.power_ups.len(), 1);
+    }
+
+    #[test]
+    fn grow_lengthens_the_collectors_paddle_until_it_runs_out() {
+        let mut board = collected(PowerUpKind::Grow);
+        let config = board.config.clone();
+        let centre = board.lhs_paddle.bound.y + board.lhs_paddle.bound.height / 2.;
+        assert_eq!(board.lhs_paddle.bound.height, config.paddle_height * config.power_ups.paddle_grow);
+        assert_eq!(board.rhs_paddle.bound.height, config.paddle_height);
+        assert_eq!(board.lhs_paddle.bound.y + board.lhs_paddle.bound.height / 2., centre);
+        run_out(&mut board);
+        assert_eq!(board.lhs_paddle.bound.height, config.paddle_height);
+    }
+
+    #[test]
+    fn shrink_shortens_the_opponents_paddle_until_it_runs_out() {
+        let mut board = collected(PowerUpKind::Shrink);
+        let config = board.config.clone();
+        assert_eq!(board.lhs_paddle.bound.height, config.paddle_height);
+        assert_eq!(board.rhs_paddle.bound.height, config.paddle_height * config.power_ups.paddle_shrink);
+        run_out(&mut board);
+        assert_eq!(board.rhs_paddle.bound.height, config.paddle_height);
+    }
+
+    #[test]
+    fn speed_burst_moves_the_ball_faster_until_it_runs_out() {
+        let mut board = collected(PowerUpKind::SpeedBurst);
+        let burst = board.config.power_ups.speed_burst;
+        assert_eq!(board.speed_burst(), burst);
+        let (x, dx) = (board.ball.bound.x, board.ball.dx);
+        board.update(TICK_DT);
+        assert!((board.ball.bound.x - (x + dx * burst * TICK_DT)).abs() < 1e-3);
+        run_out(&mut board);
+        assert_eq!(board.speed_burst(), 1.);
+    }
+
+    #[test]
+    fn goal_shrink_narrows_the_collectors_goal_until_it_runs_out() {
+        let mut board = collected(PowerUpKind::GoalShrink);
+        let config = board.config.clone();
+        assert_eq!(board.lhs_goal_height, config.goal_height * config.power_ups.goal_shrink);
+        assert_eq!(board.rhs_goal_height, config.goal_height);
+        run_out(&mut board);
+        assert_eq!(board.lhs_goal_height, config.goal_height);
+    }
+
+    #[test]
+    fn shield_blocks_one_goal() {
+        let mut board = collected(PowerUpKind::Shield);
+        assert!(board.has_shield(Side::Lhs));
+        assert!(!board.has_shield(Side::Rhs));
+        board.lhs_paddle.bound.y = 0.;
+        match run_until(&mut board, 5 * TICK_RATE, |_| true) {
+            Some(Event::WallHit(impact)) => {
+                assert!(impact.dx > 0.);
+                assert!((impact.x - board.config.ball_radius).abs() < 0.01);
+            },
+            other => panic!("expected the shield to block the ball, got {:?}", other)
+        }
+        assert!(!board.has_shield(Side::Lhs));
+        assert_eq!((board.lhs_score, board.rhs_score), (0, 0));
+    }
+
+    #[test]
+    fn shield_runs_out_unused() {
+        let mut board = collected(PowerUpKind::Shield);
+        run_out(&mut board);
+        assert!(!board.has_shield(Side::Lhs));
+    }
+
+    #[test]
+    fn collecting_again_restarts_the_timer() {
+        let mut board = collected(PowerUpKind::Grow);
+        board.ball.dx = 0.;
+        board.ball.dy = 0.;
+        for _ in 0..TICK_RATE {
+            board.update(TICK_DT);
+        }
+        let radius = board.config.ball_radius;
+        let (x, y) = (board.ball.bound.x + radius, board.ball.bound.y + radius);
+        board.power_ups.push(PowerUp { kind: PowerUpKind::Grow, x, y });
+        board.update(TICK_DT);
+        assert_eq!(board.active_effects.len(), 1);
+        assert_eq!(board.active_effects[0].remaining, board.config.power_ups.duration);
+    }
+}
@@ -0,0 +1,484 @@
+use config::GamepadConfig;
+use game;
+use game::{Input, Side};
+use input::Action;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::io::prelude::*;
+use std::rc::Rc;
+#[cfg(target_os = "linux")]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+// How often unopened joystick devices are retried, to pick up new pads.
+#[cfg(target_os = "linux")]
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+// Joystick device numbers looked at on each rescan.
+#[cfg(target_os = "linux")]
+const MAX_JOYSTICKS: u32 = 16;
+// Bits of the type byte of a Linux joystick event. Init events report the
+// state at the time the device was opened.
+#[cfg(target_os = "linux")]
+const JS_EVENT_BUTTON: u8 = 0x01;
+#[cfg(target_os = "linux")]
+const JS_EVENT_AXIS: u8 = 0x02;
+#[cfg(target_os = "linux")]
+const JS_EVENT_INIT: u8 = 0x80;
+// Stick positions are rounded to this many steps either way, so noise on a
+// held stick doesn't flood replays and netplay with inputs.
+const STICK_STEPS: f32 = 64.;
+
+/// Tells pads apart for as long as they stay connected.
+pub type PadId = u32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PadEvent {
+    Connected(PadId, String),
+    Disconnected(PadId),
+    /// An axis number and its position, from -32767 (left or up) to 32767.
+    Axis(PadId, u32, i16),
+    Button(PadId, u32, bool)
+}
+
+/// A source of raw pad events.
+pub trait GamepadBackend {
+    /// Takes the events since the last call.
+    fn poll(&mut self) -> Vec<PadEvent>;
+}
+
+/// Picks the joystick backend, or one without pads when they're turned off
+/// or the platform has no backend.
+pub fn open(config: &GamepadConfig) -> Box<dyn GamepadBackend> {
+    if !config.enabled {
+        return Box::new(VirtualGamepads::new());
+    }
+    joysticks()
+}
+
+#[cfg(target_os = "linux")]
+fn joysticks() -> Box<dyn GamepadBackend> {
+    Box::new(LinuxJoysticks::new())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn joysticks() -> Box<dyn GamepadBackend> {
+    println!("Gamepads are unsupported on this platform; playing with the keyboard and mouse.");
+    Box::new(VirtualGamepads::new())
+}
+
+/// Reads the Linux joystick devices, `/dev/input/js*`, each on a thread of
+/// its own. A pad whose device stops reading has been unplugged.
+#[cfg(target_os = "linux")]
+pub struct LinuxJoysticks {
+    sender: Sender<PadEvent>,
+    receiver: Receiver<PadEvent>,
+    // Device numbers with a reader thread, which are also the pad ids.
+    open: Vec<PadId>,
+    last_scan: Option<Instant>
+}
+
+#[cfg(target_os = "linux")]
+impl LinuxJoysticks {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        LinuxJoysticks {
+            sender,
+            receiver,
+            open: Vec::new(),
+            last_scan: None
+        }
+    }
+
+    fn scan(&mut self) {
+        for number in 0..MAX_JOYSTICKS {
+            if self.open.contains(&number) {
+                continue;
+            }
+            let file = match File::open(format!("/dev/input/js{}", number)) {
+                Ok(file) => file,
+                Err(_) => continue
+            };
+            let name = fs::read_to_string(format!("/sys/class/input/js{}/device/name", number))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_else(|_| format!("joystick {}", number));
+            self.open.push(number);
+            // Sent before the thread starts, so it arrives before any of the
+            // pad's input.
+            let _ = self.sender.send(PadEvent::Connected(number, name));
+            let sender = self.sender.clone();
+            thread::spawn(move || read_joystick(number, file, sender));
+        }
+    }
+}
+
+/// Forwards the events of one device until it goes away, or until nobody is
+/// listening any more.
+#[cfg(target_os = "linux")]
+fn read_joystick(id: PadId, mut file: File, sender: Sender<PadEvent>) {
+    // A timestamp, the value, the type and the axis or button number.
+    let mut event = [0; 8];
+    while file.read_exact(&mut event).is_ok() {
+        let value = i16::from_ne_bytes([event[4], event[5]]);
+        let number = u32::from(event[7]);
+        let pad_event = match event[6] & !JS_EVENT_INIT {
+            JS_EVENT_BUTTON => PadEvent::Button(id, number, value != 0),
+            JS_EVENT_AXIS => PadEvent::Axis(id, number, value),
+            _ => continue
+        };
+        if sender.send(pad_event).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(PadEvent::Disconnected(id));
+}
+
+#[cfg(target_os = "linux")]
+impl GamepadBackend for LinuxJoysticks {
+    fn poll(&mut self) -> Vec<PadEvent> {
+        if self.last_scan.is_none_or(|time| time.elapsed() >= RESCAN_INTERVAL) {
+            self.last_scan = Some(Instant::now());
+            self.scan();
+        }
+        let events: Vec<_> = self.receiver.try_iter().collect();
+        for event in events.iter() {
+            if let PadEvent::Disconnected(id) = *event {
+                self.open.retain(|&open| open != id);
+            }
+        }
+        events
+    }
+}
+
+/// Pads driven from code, to exercise the mapping without hardware. Clones share
+/// their events, so one can be kept to drive a copy handed to `Gamepads`.
+/// With no pads connected it also stands in when gamepads are turned off or
+/// unsupported.
+#[derive(Clone, Default)]
+pub struct VirtualGamepads {
+    events: Rc<RefCell<VecDeque<PadEvent>>>
+}
+
+impl VirtualGamepads {
+    pub fn new() -> Self {
+        VirtualGamepads::default()
+    }
+
+    /// Queues `event` for the next poll.
+    #[cfg(test)]
+    pub fn send(&self, event: PadEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+impl GamepadBackend for VirtualGamepads {
+    fn poll(&mut self) -> Vec<PadEvent> {
+        self.events.borrow_mut().drain(..).collect()
+    }
+}
+
+struct Pad {
+    id: PadId,
+    name: String,
+    side: Option<Side>,
+    // Last stick position handed on, after the deadzone.
+    stick: i16,
+    // D-pad direction held: 1 for up, -1 for down.
+    dpad: i8
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Lhs => "left",
+        Side::Rhs => "right"
+    }
+}
+
+/// The paddle input for a D-pad direction.
+fn dpad_input(side: Side, direction: i8) -> Option<Input> {
+    match direction {
+        1 => Some(Input::Up(side)),
+        -1 => Some(Input::Down(side)),
+        _ => None
+    }
+}
+
+/// A raw axis position as an `Input::Stick` position: flipped so up is
+/// positive, with the deadzone cut out and the rest stretched to full range.
+fn stick_position(raw: i16, deadzone: f32) -> i16 {
+    let amount = (-f32::from(raw) / f32::from(game::STICK_MAX)).clamp(-1., 1.);
+    if amount.abs() <= deadzone {
+        return 0;
+    }
+    let scaled = ((amount.abs() - deadzone) / (1. - deadzone) * STICK_STEPS).round() / STICK_STEPS;
+    (amount.signum() * scaled * f32::from(game::STICK_MAX)) as i16
+}
+
+/// Turns pad events into actions and hands each human paddle to a pad, in
+/// the order the pads connect. When a pad is unplugged its paddle stops and
+/// goes to the next pad still waiting for one. Pads without a paddle can
+/// still confirm and pause.
+pub struct Gamepads {
+    backend: Box<dyn GamepadBackend>,
+    config: GamepadConfig,
+    sides: Vec<Side>,
+    pads: Vec<Pad>
+}
+
+impl Gamepads {
+    /// Pads steer the paddles in `sides`, handed out in that order.
+    pub fn new(backend: Box<dyn GamepadBackend>, config: &GamepadConfig, sides: &[Side]) -> Self {
+        Gamepads {
+            backend,
+            config: config.clone(),
+            sides: sides.to_vec(),
+            pads: Vec::new()
+        }
+    }
+
+    /// Takes the actions since the last call, with whether each is pressed.
+    pub fn poll(&mut self) -> Vec<(Action, bool)> {
+        let mut actions = Vec::new();
+        for event in self.backend.poll() {
+            self.handle_event(event, &mut actions);
+        }
+        actions
+    }
+
+    fn free_side(&self) -> Option<Side> {
+        self.sides.iter().cloned().find(|&side| self.pads.iter().all(|pad| pad.side != Some(side)))
+    }
+
+    fn handle_event(&mut self, event: PadEvent, actions: &mut Vec<(Action, bool)>) {
+        match event {
+            PadEvent::Connected(id, name) => {
+                let side = self.free_side();
+                match side {
+                    Some(side) => println!("Gamepad \"{}\" connected, steering the {} paddle.", name, side_name(side)),
+                    None => println!("Gamepad \"{}\" connected.", name)
+                }
+                self.pads.push(Pad { id, name, side, stick: 0, dpad: 0 });
+            },
+            PadEvent::Disconnected(id) => {
+                let index = match self.pads.iter().position(|pad| pad.id == id) {
+                    Some(index) => index,
+                    None => return
+                };
+                let pad = self.pads.remove(index);
+                println!("Gamepad \"{}\" disconnected.", pad.name);
+                let side = match pad.side {
+                    Some(side) => side,
+                    None => return
+                };
+                if pad.stick != 0 {
+                    actions.push((Action::Board(Input::Stick(side, 0)), false));
+                }
+                if let Some(input) = dpad_input(side, pad.dpad) {
+                    actions.push((Action::Board(input), false));
+                }
+                if let Some(waiting) = self.pads.iter_mut().find(|pad| pad.side.is_none()) {
+                    waiting.side = Some(side);
+                    println!("Gamepad \"{}\" now steers the {} paddle.", waiting.name, side_name(side));
+                }
+            },
+            PadEvent::Axis(id, axis, value) => {
+                let config = &self.config;
+                let pad = match self.pads.iter_mut().find(|pad| pad.id == id) {
+                    Some(pad) => pad,
+                    None => return
+                };
+                let side = match pad.side {
+                    Some(side) => side,
+                    None => return
+                };
+                if axis == config.stick_axis {
+                    let stick = stick_position(value, config.deadzone);
+                    if stick != pad.stick {
+                        pad.stick = stick;
+                        actions.push((Action::Board(Input::Stick(side, stick)), stick != 0));
+                    }
+                } else if axis == config.dpad_axis {
+                    let dpad = -value.signum() as i8;
+                    if dpad != pad.dpad {
+                        if let Some(input) = dpad_input(side, pad.dpad) {
+                            actions.push((Action::Board(input), false));
+                        }
+                        if let Some(input) = dpad_input(side, dpad) {
+                            actions.push((Action::Board(input), true));
+                        }
+                        pad.dpad = dpad;
+                    }
+                }
+            },
+            PadEvent::Button(id, button, is_pressed) => {
+                if !self.pads.iter().any(|pad| pad.id == id) {
+                    return;
+                }
+                if button == self.config.confirm_button {
+                    actions.push((Action::Confirm, is_pressed));
+                } else if button == self.config.pause_button {
+                    actions.push((Action::Pause, is_pressed));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use game::Board;
+
+    /// Two human paddles steered by virtual pads.
+    struct Rig {
+        pads: VirtualGamepads,
+        gamepads: Gamepads,
+        board: Board
+    }
+
+    impl Rig {
+        fn new() -> Self {
+            let config = GameConfig::default();
+            let pads = VirtualGamepads::new();
+            let gamepads = Gamepads::new(Box::new(pads.clone()), &config.gamepad, &[Side::Lhs, Side::Rhs]);
+            let mut board = Board::new(config);
+            board.set_controller(Side::Lhs, game::Controller::Human);
+            board.set_controller(Side::Rhs, game::Controller::Human);
+            board.start_game(true);
+            Rig { pads, gamepads, board }
+        }
+
+        /// Feeds `events` to the virtual pads and hands the resulting paddle
+        /// inputs to the board, returning the other actions.
+        fn feed(&mut self, events: &[PadEvent]) -> Vec<(Action, bool)> {
+            for event in events {
+                self.pads.send(event.clone());
+            }
+            let mut others = Vec::new();
+            for (action, is_pressed) in self.gamepads.poll() {
+                match action {
+                    Action::Board(input) => self.board.handle_input(input, is_pressed),
+                    action => others.push((action, is_pressed))
+                }
+            }
+            others
+        }
+
+        fn connect(&mut self, id: PadId) {
+            self.feed(&[PadEvent::Connected(id, format!("virtual pad {}", id))]);
+        }
+
+        fn stick(&mut self, id: PadId, value: i16) {
+            let axis = self.board.config.gamepad.stick_axis;
+            self.feed(&[PadEvent::Axis(id, axis, value)]);
+        }
+
+        fn dpad(&mut self, id: PadId, value: i16) {
+            let axis = self.board.config.gamepad.dpad_axis;
+            self.feed(&[PadEvent::Axis(id, axis, value)]);
+        }
+
+        fn accel(&self) -> f32 {
+            self.board.config.player_paddle_accel
+        }
+
+        fn assert_ddy(&self, side: Side, expected: f32) {
+            let ddy = self.board.paddle(side).ddy;
+            assert!((ddy - expected).abs() <= self.accel() / STICK_STEPS,
+                    "the {} paddle accelerates at {}, expected {}", side_name(side), ddy, expected);
+        }
+    }
+
+    #[test]
+    fn stick_scales_the_paddle_accel() {
+        let mut rig = Rig::new();
+        let (accel, deadzone) = (rig.accel(), rig.board.config.gamepad.deadzone);
+        rig.connect(0);
+        rig.connect(1);
+        rig.stick(0, -i16::MAX / 2);
+        rig.stick(1, i16::MAX);
+        rig.assert_ddy(Side::Lhs, accel * (0.5 - deadzone) / (1. - deadzone));
+        rig.assert_ddy(Side::Rhs, -accel);
+        rig.stick(1, 0);
+        rig.assert_ddy(Side::Rhs, 0.);
+    }
+
+    #[test]
+    fn stick_inside_the_deadzone_is_ignored() {
+        let deadzone = GameConfig::default().gamepad.deadzone;
+        let nudge = (deadzone / 2. * f32::from(i16::MAX)) as i16;
+        assert_eq!(stick_position(nudge, deadzone), 0);
+        assert_eq!(stick_position(-nudge, deadzone), 0);
+        assert!(stick_position(-i16::MAX, deadzone) > 0);
+        assert_eq!(stick_position(-i16::MAX, deadzone), game::STICK_MAX);
+
+        let mut rig = Rig::new();
+        rig.connect(0);
+        rig.stick(0, -i16::MAX);
+        rig.stick(0, -nudge);
+        rig.assert_ddy(Side::Lhs, 0.);
+    }
+
+    #[test]
+    fn dpad_steers_at_full_accel() {
+        let mut rig = Rig::new();
+        let accel = rig.accel();
+        rig.connect(0);
+        rig.dpad(0, -i16::MAX);
+        rig.assert_ddy(Side::Lhs, accel);
+        rig.dpad(0, i16::MAX);
+        rig.assert_ddy(Side::Lhs, -accel);
+        rig.dpad(0, 0);
+        rig.assert_ddy(Side::Lhs, 0.);
+    }
+
+    #[test]
+    fn pads_take_paddles_in_the_order_they_connect() {
+        let mut rig = Rig::new();
+        let accel = rig.accel();
+        rig.connect(7);
+        rig.connect(3);
+        rig.stick(7, -i16::MAX);
+        rig.assert_ddy(Side::Lhs, accel);
+        rig.assert_ddy(Side::Rhs, 0.);
+        rig.stick(3, i16::MAX);
+        rig.assert_ddy(Side::Lhs, accel);
+        rig.assert_ddy(Side::Rhs, -accel);
+    }
+
+    #[test]
+    fn unplugged_paddle_stops_and_passes_to_a_spare_pad() {
+        let mut rig = Rig::new();
+        let accel = rig.accel();
+        rig.connect(0);
+        rig.connect(1);
+        rig.connect(2);
+        rig.stick(1, i16::MAX);
+        rig.stick(2, -i16::MAX);
+        rig.assert_ddy(Side::Rhs, -accel);
+        rig.feed(&[PadEvent::Disconnected(1)]);
+        rig.assert_ddy(Side::Rhs, 0.);
+        rig.stick(2, -i16::MAX + 1);
+        rig.assert_ddy(Side::Rhs, accel);
+    }
+
+    #[test]
+    fn spare_pads_can_still_confirm_and_pause() {
+        let mut rig = Rig::new();
+        let (confirm, pause) = (rig.board.config.gamepad.confirm_button, rig.board.config.gamepad.pause_button);
+        rig.connect(0);
+        rig.connect(1);
+        rig.connect(2);
+        assert_eq!(rig.feed(&[PadEvent::Button(2, confirm, true)]), vec![(Action::Confirm, true)]);
+        assert_eq!(rig.feed(&[PadEvent::Button(2, pause, false)]), vec![(Action::Pause, false)]);
+        assert!(rig.feed(&[PadEvent::Button(9, confirm, true)]).is_empty());
+    }
+}
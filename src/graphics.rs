@@ -1,136 +1,208 @@
 extern crate glium;
 
+use assets;
+use effects;
+use font;
 use game;
-use std::io::prelude::*;
-use std::fs::File;
-use glium::Surface;
+use state;
+use theme;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use glium::{DisplayBuild, Surface};
+use glium::backend::glutin_backend::GlutinFacade;
 
-const VERT_PATH: &'static str = "shaders/vert.glsl";
-const GEOM_PATH: &'static str = "shaders/geom.glsl";
-const FRAG_PATH: &'static str = "shaders/frag.glsl";
+const SHADERS: [&str; 3] = [assets::VERT_SHADER, assets::GEOM_SHADER, assets::FRAG_SHADER];
 const BATCH_SIZE: u32 = 100;
-const BORDER_WIDTH: f32 = 2.;
-const DIGIT_LINE_SIZE: f32 = 20.;
-const DIGIT_LINE_THICKNESS: f32 = 5.;
-const DIGIT_SPACING: f32 = 2. * DIGIT_LINE_THICKNESS;
-const NET_WIDTH: f32 = 1.;
-const SCORE_PADDING: f32 = 20.;
-const NUM_NET_SEGMENTS: u32 = 20;
+// How often dev mode looks for edited shaders.
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// A blinking score toggles this often while the board holds after a goal.
+const BLINK_TICKS: u32 = game::TICK_RATE / 8;
+// Fills the bars around the frame when the window's shape doesn't match it.
+const LETTERBOX_COLOR: theme::Color = [0., 0., 0., 1.];
 
 #[derive(Copy, Clone)]
 struct Vertex {
     position: [f32; 2],
     dimension: [f32; 2],
+    color: [f32; 4],
+    radius: f32,
 }
 
-implement_vertex!(Vertex, position, dimension);
+implement_vertex!(Vertex, position, dimension, color, radius);
 
-pub struct BoardRenderer {
-    program: glium::Program,
-    vertex_buffer: glium::VertexBuffer<Vertex>,
-    shape: Vec<Vertex>,
-    projection: [f32; 2],
-    batch_index: u32
+/// What fills a rect when it's drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Rect,
+    /// The largest circle centred in the rect, or a capsule when the rect
+    /// isn't square.
+    Circle,
+    /// A rect with corners of the given radius.
+    RoundedRect(f32)
 }
 
-impl BoardRenderer {
-    pub fn new(dpy: &glium::backend::Facade, width: u32, height: u32) -> Result<Self, ()> {
-        let shape: Vec<Vertex> = (0..BATCH_SIZE).map(|_| Vertex {
-                position: [0., 0.],
-                dimension: [0., 0.]
-            })
-            .collect();
-        let vertex_buffer = glium::VertexBuffer::dynamic(dpy, &shape).map_err(|_| {})?;
+impl Shape {
+    /// Corner radius in board units, capped so corners never overlap.
+    pub fn radius(&self, rect: &game::Rect) -> f32 {
+        let max = rect.width.min(rect.height) / 2.;
+        match *self {
+            Shape::Rect => 0.,
+            Shape::Circle => max,
+            Shape::RoundedRect(radius) => radius.max(0.).min(max)
+        }
+    }
+
+    /// Signed distance from `(x, y)` to the shape's edge, negative inside.
+    /// Matches the fragment shader, so every backend agrees on the outline.
+    pub fn distance(&self, rect: &game::Rect, x: f32, y: f32) -> f32 {
+        let radius = self.radius(rect);
+        let qx = (x - rect.x - rect.width / 2.).abs() - rect.width / 2. + radius;
+        let qy = (y - rect.y - rect.height / 2.).abs() - rect.height / 2. + radius;
+        let outside = qx.max(0.).hypot(qy.max(0.));
+        outside + qx.max(qy).min(0.) - radius
+    }
+}
 
-        fn read_file(path: &str) -> Result<String, ()> {
-            let mut file = File::open(path).map_err(|_| {})?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents).map_err(|_| {})?;
-            Ok(contents)
-        }
-
-        let program = glium::Program::from_source(dpy,
-            &read_file(VERT_PATH)?,
-            &read_file(FRAG_PATH)?,
-            Some(&read_file(GEOM_PATH)?))
-                .map_err(|e| {
-                    match e {
-                        glium::CompilationError(log) |
-                        glium::LinkingError(log) => println!("{}", log.trim()),
-                        _ => {}
-                    }
-                })?;
+/// Counts frames over half second windows.
+struct FpsCounter {
+    frames: u32,
+    since: Instant,
+    fps: u32
+}
 
-        Ok(BoardRenderer {
-            program: program,
-            vertex_buffer: vertex_buffer,
-            shape: shape,
-            projection: [width as f32, height as f32],
-            batch_index: 0
-        })
+impl FpsCounter {
+    fn new() -> Self {
+        FpsCounter {
+            frames: 0,
+            since: Instant::now(),
+            fps: 0
+        }
     }
 
-    pub fn handle_frame_resize(&mut self, width: u32, height: u32) {
-        self.projection = [
-            width as f32,
-            height as f32
-        ];
+    fn frame(&mut self) -> u32 {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1000000000.;
+        if seconds >= 0.5 {
+            self.fps = (self.frames as f32 / seconds).round() as u32;
+            self.frames = 0;
+            self.since = Instant::now();
+        }
+        self.fps
     }
+}
 
-    fn draw_rect(&mut self, frame: &mut glium::Frame, board: &game::Board, rect: game::Rect) {
-        if self.batch_index == BATCH_SIZE {
-            self.flush_draw_batch(frame, board);
+/// Notices edits to the shader files by polling their modification times.
+/// Only files in the asset directories can change; adding or removing one
+/// counts as a change too.
+struct ShaderWatch {
+    modified: Vec<Option<(PathBuf, SystemTime)>>,
+    last_poll: Instant
+}
+
+impl ShaderWatch {
+    fn new(assets: &assets::Assets) -> Self {
+        ShaderWatch {
+            modified: ShaderWatch::modified_times(assets),
+            last_poll: Instant::now()
         }
-        self.shape[self.batch_index as usize] = Vertex {
-            position: [rect.x, rect.y],
-            dimension: [rect.width, rect.height]
-        };
-        self.batch_index += 1;
     }
 
-    fn draw_digit(&mut self, frame: &mut glium::Frame, board: &game::Board, digit: u8, x: f32, y: f32) {
+    fn modified_times(assets: &assets::Assets) -> Vec<Option<(PathBuf, SystemTime)>> {
+        SHADERS.iter()
+            .map(|name| assets.locate(name).and_then(|path| {
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((path, modified))
+            }))
+            .collect()
+    }
+
+    /// Whether any shader changed since the last call that returned true.
+    fn poll(&mut self, assets: &assets::Assets) -> bool {
+        if self.last_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = ShaderWatch::modified_times(assets);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+/// Compiles the shaders, returning the compile log on failure.
+fn load_program(dpy: &GlutinFacade, assets: &assets::Assets) -> Result<glium::Program, String> {
+    let read = |name| assets.load_text(name).map_err(|e| e.to_string());
+    glium::Program::from_source(dpy,
+        &read(assets::VERT_SHADER)?,
+        &read(assets::FRAG_SHADER)?,
+        Some(&read(assets::GEOM_SHADER)?))
+            .map_err(|e| match e {
+                glium::CompilationError(log) |
+                glium::LinkingError(log) => log.trim().to_string(),
+                e => format!("{:?}", e)
+            })
+}
+
+/// Something board-space shapes can be filled on. All of the layout, text and
+/// digits included, is built on `draw_shape` alone, so every backend draws
+/// exactly the same rects, rounded rects and circles. Backends that can print
+/// characters directly may still override `draw_text`.
+pub trait Canvas {
+    fn draw_shape(&mut self, board: &game::Board, rect: game::Rect, shape: Shape, color: theme::Color);
+
+    fn draw_rect(&mut self, board: &game::Board, rect: game::Rect, color: theme::Color) {
+        self.draw_shape(board, rect, Shape::Rect, color);
+    }
+
+    fn draw_digit(&mut self, board: &game::Board, digit: u8, x: f32, y: f32, color: theme::Color) {
+        let line_size = board.config.graphics.digit_line_size;
+        let line_thickness = board.config.graphics.digit_line_thickness;
         let lines = [
             game::Rect {
                 x: 0.,
-                y: DIGIT_LINE_SIZE * 2. - DIGIT_LINE_THICKNESS,
-                width: DIGIT_LINE_SIZE,
-                height: DIGIT_LINE_THICKNESS
+                y: line_size * 2. - line_thickness,
+                width: line_size,
+                height: line_thickness
             },
             game::Rect {
-                x: DIGIT_LINE_SIZE - DIGIT_LINE_THICKNESS,
-                y: DIGIT_LINE_SIZE,
-                width: DIGIT_LINE_THICKNESS,
-                height: DIGIT_LINE_SIZE
+                x: line_size - line_thickness,
+                y: line_size,
+                width: line_thickness,
+                height: line_size
             },
             game::Rect {
-                x: DIGIT_LINE_SIZE - DIGIT_LINE_THICKNESS,
+                x: line_size - line_thickness,
                 y: 0.,
-                width: DIGIT_LINE_THICKNESS,
-                height: DIGIT_LINE_SIZE
+                width: line_thickness,
+                height: line_size
             },
             game::Rect {
                 x: 0.,
                 y: 0.,
-                width: DIGIT_LINE_SIZE,
-                height: DIGIT_LINE_THICKNESS
+                width: line_size,
+                height: line_thickness
             },
             game::Rect {
                 x: 0.,
                 y: 0.,
-                width: DIGIT_LINE_THICKNESS,
-                height: DIGIT_LINE_SIZE
+                width: line_thickness,
+                height: line_size
             },
             game::Rect {
                 x: 0.,
-                y: DIGIT_LINE_SIZE,
-                width: DIGIT_LINE_THICKNESS,
-                height: DIGIT_LINE_SIZE
+                y: line_size,
+                width: line_thickness,
+                height: line_size
             },
             game::Rect {
                 x: 0.,
-                y: DIGIT_LINE_SIZE - DIGIT_LINE_THICKNESS / 2.,
-                width: DIGIT_LINE_SIZE,
-                height: DIGIT_LINE_THICKNESS
+                y: line_size - line_thickness / 2.,
+                width: line_size,
+                height: line_thickness
             }
         ];
         let digit_to_lines: [Vec<u8>; 10] = [
@@ -146,11 +218,14 @@ impl BoardRenderer {
             vec![0, 1, 2, 5, 6]
         ];
         for &i in digit_to_lines[digit as usize].iter() {
-            self.draw_rect(frame, board, lines[i as usize].translate(x, y));
+            self.draw_rect(board, lines[i as usize].translate(x, y), color);
         }
     }
 
-    fn draw_number(&mut self, frame: &mut glium::Frame, board: &game::Board, number: u32, x: f32, y: f32, align_left: bool) {
+    fn draw_number(&mut self, board: &game::Board, number: u32, x: f32, y: f32, align_left: bool,
+                   color: theme::Color) {
+        let line_size = board.config.graphics.digit_line_size;
+        let spacing = board.config.graphics.digit_spacing;
         let mut digits = Vec::new();
         if number == 0 {
             digits.push(0u8);
@@ -164,72 +239,498 @@ impl BoardRenderer {
         let mut cursor = if align_left {
             (x, y)
         } else {
-            (x - (digits.len() as f32) * (DIGIT_LINE_SIZE + DIGIT_SPACING) + DIGIT_SPACING, y)
+            (x - (digits.len() as f32) * (line_size + spacing) + spacing, y)
         };
         for &digit in digits.iter().rev() {
             let (x, y) = cursor;
-            self.draw_digit(frame, board, digit, x, y);
-            cursor = (x + DIGIT_LINE_SIZE + DIGIT_SPACING, y);
+            self.draw_digit(board, digit, x, y, color);
+            cursor = (x + line_size + spacing, y);
+        }
+    }
+
+    /// Backends that can print characters directly may override this.
+    fn draw_text(&mut self, board: &game::Board, text: &str,
+                 pos: (f32, f32), size: font::Size, align: font::Align, color: theme::Color) {
+        for rect in font::text_rects(text, pos.0, pos.1, size, align) {
+            self.draw_rect(board, rect, color);
+        }
+    }
+
+    fn draw_menu(&mut self, board: &game::Board, items: &[state::MenuItem], selected: usize,
+                 color: theme::Color) {
+        let size = font::Size::Medium;
+        let row_height = 2. * size.height();
+        let top = board.height / 2. + (items.len() - 1) as f32 * row_height / 2. - size.height() / 2.;
+        for (i, &item) in items.iter().enumerate() {
+            let y = top - i as f32 * row_height;
+            let label = if i == selected { format!("> {} <", item.label()) } else { item.label().to_string() };
+            self.draw_text(board, &label, (board.width / 2., y), size, font::Align::Center, color);
+        }
+    }
+
+    fn draw_power_ups(&mut self, board: &game::Board, theme: &theme::Theme) {
+        let radius = board.config.power_ups.radius;
+        let size = font::Size::Small;
+        for power_up in board.power_ups.iter() {
+            self.draw_shape(board, game::Rect {
+                x: power_up.x - radius, y: power_up.y - radius,
+                width: 2. * radius, height: 2. * radius
+            }, Shape::Circle, theme.ball);
+            self.draw_text(board, power_up.kind.symbol(), (power_up.x, power_up.y - size.height() / 2.),
+                           size, font::Align::Center, theme.background);
+        }
+    }
+
+    /// A row per effect in play, stacked upwards from `y` above the name of
+    /// whoever collected it: its symbol and a bar that runs down with it.
+    fn draw_effect_timers(&mut self, board: &game::Board, lhs_x: f32, rhs_x: f32, y: f32,
+                          theme: &theme::Theme) {
+        let size = font::Size::Small;
+        let row_height = 1.5 * size.height();
+        let gap = size.pixel();
+        let bar_width = 3. * board.config.graphics.digit_line_size;
+        let mut rows = (0, 0);
+        for effect in board.active_effects.iter() {
+            let symbol = effect.kind.symbol();
+            let symbol_width = font::text_width(symbol, size);
+            let width = bar_width * (effect.remaining / board.config.power_ups.duration).min(1.);
+            let (row, x, bar_x, align, color) = match effect.side {
+                game::Side::Lhs => {
+                    rows.0 += 1;
+                    (rows.0, lhs_x, lhs_x - symbol_width - gap - width, font::Align::Right, theme.lhs_score)
+                },
+                game::Side::Rhs => {
+                    rows.1 += 1;
+                    (rows.1, rhs_x, rhs_x + symbol_width + gap, font::Align::Left, theme.rhs_score)
+                }
+            };
+            let row_y = y + row as f32 * row_height - size.height();
+            self.draw_text(board, symbol, (x, row_y), size, align, color);
+            self.draw_rect(board, game::Rect {
+                x: bar_x, y: row_y + size.height() / 2. - gap / 2.,
+                width, height: gap
+            }, color);
+        }
+    }
+
+    /// Lays out the board and the match state around it.
+    fn draw_scene(&mut self, board: &game::Board, flow: &state::Match, effects: &effects::Effects,
+                  fps: Option<u32>) {
+        let config = &board.config.graphics;
+        let theme = config.theme();
+        let state = flow.state();
+        let paddle_shape = Shape::RoundedRect(config.paddle_corner_radius);
+        let lhs_paddle_color = theme::mix(theme.lhs_paddle, theme::flash_target(theme.lhs_paddle),
+                                          effects.flash(board, game::Side::Lhs));
+        let rhs_paddle_color = theme::mix(theme.rhs_paddle, theme::flash_target(theme.rhs_paddle),
+                                          effects.flash(board, game::Side::Rhs));
+        self.draw_shape(board, board.lhs_paddle.bound, paddle_shape, lhs_paddle_color);
+        self.draw_shape(board, board.rhs_paddle.bound, paddle_shape, rhs_paddle_color);
+        let lhs_goal_border_height = (board.height - board.lhs_goal_height) / 2.;
+        let rhs_goal_border_height = (board.height - board.rhs_goal_height) / 2.;
+        self.draw_rect(board, game::Rect {
+            x: -config.border_width, y: 0., 
+            width: config.border_width, height: lhs_goal_border_height
+        }, theme.border);
+        self.draw_rect(board, game::Rect {
+            x: -config.border_width, y: board.height - lhs_goal_border_height, 
+            width: config.border_width, height: lhs_goal_border_height
+        }, theme.border);
+        self.draw_rect(board, game::Rect {
+            x: -config.border_width, y: -config.border_width,
+            width: board.width + 2. * config.border_width, height: config.border_width
+        }, theme.border);
+        self.draw_rect(board, game::Rect {
+            x: -config.border_width, y: board.height,
+            width: board.width + 2. * config.border_width, height: config.border_width
+        }, theme.border);
+        self.draw_rect(board, game::Rect {
+            x: board.width, y: 0.,
+            width: config.border_width, height: rhs_goal_border_height
+        }, theme.border);
+        self.draw_rect(board, game::Rect {
+            x: board.width, y: board.height - rhs_goal_border_height,
+            width: config.border_width, height: rhs_goal_border_height
+        }, theme.border);
+        if board.has_shield(game::Side::Lhs) {
+            self.draw_rect(board, game::Rect {
+                x: -config.border_width, y: lhs_goal_border_height,
+                width: config.border_width, height: board.lhs_goal_height
+            }, theme.lhs_paddle);
+        }
+        if board.has_shield(game::Side::Rhs) {
+            self.draw_rect(board, game::Rect {
+                x: board.width, y: rhs_goal_border_height,
+                width: config.border_width, height: board.rhs_goal_height
+            }, theme.rhs_paddle);
+        }
+        let hidden_score = match *state {
+            state::State::PointScored { scorer, remaining } if (remaining / BLINK_TICKS) % 2 == 1 => Some(scorer),
+            _ => None
+        };
+        let lhs_score_x = board.width / 2. - config.score_padding;
+        let rhs_score_x = board.width / 2. + config.score_padding;
+        if hidden_score != Some(game::Side::Lhs) {
+            self.draw_number(board, board.lhs_score, lhs_score_x, config.score_padding, false, theme.lhs_score);
+        }
+        if hidden_score != Some(game::Side::Rhs) {
+            self.draw_number(board, board.rhs_score, rhs_score_x, config.score_padding, true, theme.rhs_score);
+        }
+        // The middle of the board is taken by menus and the countdown.
+        let has_overlay = !matches!(*state, state::State::Playing | state::State::PointScored { .. });
+        if !has_overlay {
+            for i in 0..config.net_segments {
+                self.draw_rect(board, game::Rect {
+                    x: board.width / 2., y: (i as f32 + 0.25) * board.height / (config.net_segments as f32),
+                    width: config.net_width, height: board.height / (2 * config.net_segments) as f32
+                }, theme.net);
+            }
+        }
+        let name_y = config.score_padding + 2. * config.digit_line_size + config.digit_spacing;
+        self.draw_text(board, flow.name(game::Side::Lhs), (lhs_score_x, name_y), font::Size::Small,
+                       font::Align::Right, theme.lhs_score);
+        self.draw_text(board, flow.name(game::Side::Rhs), (rhs_score_x, name_y), font::Size::Small,
+                       font::Align::Left, theme.rhs_score);
+        if !has_overlay {
+            self.draw_power_ups(board, &theme);
+            self.draw_effect_timers(board, lhs_score_x, rhs_score_x, name_y + font::Size::Small.height(), &theme);
+        }
+        effects.draw(self, board, &theme);
+        let heading = match *state {
+            state::State::Title { .. } => Some(("PONG".to_string(), font::Size::Large)),
+            state::State::Countdown { remaining } => {
+                let seconds = remaining.div_ceil(game::TICK_RATE);
+                self.draw_shape(board, effects.ball_rect(board, board.ball.bound), Shape::Circle, theme.ball);
+                Some((seconds.to_string(), font::Size::Large))
+            },
+            state::State::Paused { .. } => Some(("Paused".to_string(), font::Size::Large)),
+            state::State::GameOver { lhs_won, .. } => {
+                let winner = if lhs_won { game::Side::Lhs } else { game::Side::Rhs };
+                Some((format!("{} wins", flow.name(winner)), font::Size::Medium))
+            },
+            state::State::Playing | state::State::PointScored { .. } => {
+                self.draw_shape(board, effects.ball_rect(board, board.ball.bound), Shape::Circle, theme.ball);
+                None
+            }
+        };
+        let menu = flow.menu();
+        if let Some((text, size)) = heading {
+            // Sits above the menu, or above the ball when there is none.
+            let rows = menu.map_or(1, |(items, _)| items.len() + 1);
+            let y = board.height / 2. + rows as f32 * font::Size::Medium.height();
+            self.draw_text(board, &text, (board.width / 2., y), size, font::Align::Center, theme.text);
+        }
+        if let Some((items, selected)) = menu {
+            self.draw_menu(board, items, selected, theme.text);
+        }
+        if let Some(fps) = fps {
+            let y = board.height - font::Size::Small.height() - config.border_width;
+            self.draw_text(board, &format!("{} fps", fps), (config.border_width, y),
+                           font::Size::Small, font::Align::Left, theme.text);
         }
     }
+}
+
+/// Draws frames of a match to some output.
+pub trait Renderer {
+    fn handle_frame_resize(&mut self, width: u32, height: u32);
+    fn draw(&mut self, board: &game::Board, flow: &state::Match, effects: &effects::Effects);
+}
+
+/// Settings for the game window, windowed or fullscreen on the primary
+/// monitor. `width` and `height` are in points, which HiDPI screens show
+/// with more than one pixel each.
+pub fn window_builder(width: u32, height: u32, fullscreen: bool) -> glium::glutin::WindowBuilder<'static> {
+    let builder = glium::glutin::WindowBuilder::new()
+        .with_title("Pong")
+        .with_gl(
+            glium::glutin::GlRequest::Specific(
+                glium::glutin::Api::OpenGl,
+                (3, 3)))
+        .with_dimensions(width, height);
+    if fullscreen {
+        builder.with_fullscreen(glium::glutin::get_primary_monitor())
+    } else {
+        builder
+    }
+}
+
+pub struct BoardRenderer {
+    dpy: GlutinFacade,
+    assets: assets::Assets,
+    program: glium::Program,
+    vertex_buffer: glium::VertexBuffer<Vertex>,
+    shape: Vec<Vertex>,
+    projection: [f32; 2],
+    // Extra camera offset for screen shake, in board units.
+    shake: [f32; 2],
+    // Size of the frame, the board with its padding, in board units.
+    width: f32,
+    height: f32,
+    // Framebuffer pixels per board unit.
+    scale: f32,
+    framebuffer: (u32, u32),
+    integer_scale: bool,
+    fullscreen: bool,
+    // Window size in points to go back to when leaving fullscreen.
+    windowed_size: (u32, u32),
+    fps: Option<FpsCounter>,
+    shader_watch: Option<ShaderWatch>,
+    // Log of the last failed shader reload, shown until one succeeds.
+    shader_error: Option<String>,
+    batch_index: u32
+}
+
+impl BoardRenderer {
+    /// Fits a `width` by `height` frame, in board units, into the window,
+    /// as large as it goes while keeping its shape.
+    pub fn new(dpy: &GlutinFacade, assets: &assets::Assets, width: f32, height: f32) -> Result<Self, ()> {
+        let shape: Vec<Vertex> = (0..BATCH_SIZE).map(|_| Vertex {
+                position: [0., 0.],
+                dimension: [0., 0.],
+                color: [0., 0., 0., 0.],
+                radius: 0.
+            })
+            .collect();
+        let vertex_buffer = glium::VertexBuffer::dynamic(dpy, &shape).map_err(|_| {})?;
+
+        let program = load_program(dpy, assets).map_err(|log| println!("{}", log))?;
+
+        Ok(BoardRenderer {
+            dpy: dpy.clone(),
+            assets: assets.clone(),
+            program,
+            vertex_buffer,
+            shape,
+            projection: [width, height],
+            shake: [0., 0.],
+            width,
+            height,
+            scale: 1.,
+            // Fitted to the real size on the first draw.
+            framebuffer: (0, 0),
+            integer_scale: false,
+            fullscreen: false,
+            windowed_size: (0, 0),
+            fps: None,
+            shader_watch: None,
+            shader_error: None,
+            batch_index: 0
+        })
+    }
+
+    /// Shows the frame rate in the top left corner.
+    pub fn set_show_fps(&mut self, show: bool) {
+        self.fps = if show { Some(FpsCounter::new()) } else { None };
+    }
+
+    /// Only scales the frame by whole pixels per board unit, for crisp
+    /// edges, leaving wider bars around it.
+    pub fn set_integer_scale(&mut self, integer_scale: bool) {
+        self.integer_scale = integer_scale;
+        self.framebuffer = (0, 0);
+    }
+
+    /// Switches between a window and fullscreen on the primary monitor,
+    /// which comes back to the window's last size.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        if fullscreen == self.fullscreen {
+            return;
+        }
+        if fullscreen {
+            if let Some(size) = self.dpy.get_window().and_then(|window| window.get_inner_size()) {
+                self.windowed_size = size;
+            }
+        }
+        let (width, height) = self.windowed_size;
+        match window_builder(width, height, fullscreen).rebuild_glium(&self.dpy) {
+            Ok(()) => self.fullscreen = fullscreen,
+            Err(e) => println!("Can't switch fullscreen {}: {}", if fullscreen { "on" } else { "off" }, e)
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = !self.fullscreen;
+        self.set_fullscreen(fullscreen);
+    }
+
+    /// Scales the frame to fill as much of a `width` by `height` pixel
+    /// framebuffer as its shape allows.
+    fn fit(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            // Minimized; keep the last fit until there's something to fill.
+            return;
+        }
+        self.framebuffer = (width, height);
+        let scale = (width as f32 / self.width).min(height as f32 / self.height);
+        // Below one pixel per unit there's no whole number to round down to.
+        self.scale = if self.integer_scale && scale >= 1. { scale.floor() } else { scale };
+        self.projection = [width as f32 / self.scale, height as f32 / self.scale];
+    }
+
+    /// The part of the framebuffer the frame fills, with the bars left out.
+    fn viewport(&self) -> glium::Rect {
+        let (width, height) = self.framebuffer;
+        let frame_width = ((self.width * self.scale).round() as u32).min(width);
+        let frame_height = ((self.height * self.scale).round() as u32).min(height);
+        glium::Rect {
+            left: (width - frame_width) / 2,
+            bottom: (height - frame_height) / 2,
+            width: frame_width,
+            height: frame_height
+        }
+    }
+
+    /// Recompiles the shaders whenever they change on disk. A shader that
+    /// fails to build leaves the last good program in use and shows its
+    /// compile log over the game.
+    pub fn set_watch_shaders(&mut self, watch: bool) {
+        self.shader_watch = if watch { Some(ShaderWatch::new(&self.assets)) } else { None };
+        if self.shader_watch.as_ref().is_some_and(|watch| watch.modified.iter().all(Option::is_none)) {
+            println!("None of the shaders are in an asset directory, so edits won't be picked up; \
+                      point --assets at a copy of shaders/.");
+        }
+        self.shader_error = None;
+    }
+
+    fn reload_changed_shaders(&mut self) {
+        let assets = &self.assets;
+        if !self.shader_watch.as_mut().is_some_and(|watch| watch.poll(assets)) {
+            return;
+        }
+        match load_program(&self.dpy, &self.assets) {
+            Ok(program) => {
+                println!("Reloaded shaders.");
+                self.program = program;
+                self.shader_error = None;
+            },
+            Err(log) => {
+                println!("Shader reload failed, keeping the last good shaders:\n{}", log);
+                self.shader_error = Some(log);
+            }
+        }
+    }
+
+    fn draw_shape(&mut self, frame: &mut glium::Frame, board: &game::Board, rect: game::Rect,
+                  shape: Shape, color: theme::Color) {
+        if self.batch_index == BATCH_SIZE {
+            self.flush_draw_batch(frame, board);
+        }
+        self.shape[self.batch_index as usize] = Vertex {
+            position: [rect.x, rect.y],
+            dimension: [rect.width, rect.height],
+            color,
+            radius: shape.radius(&rect)
+        };
+        self.batch_index += 1;
+    }
+
+    /// Where the board's origin is drawn, in board units from the bottom
+    /// left of the window, leaving out the screen shake. It's kept on a
+    /// whole pixel so integer scaling stays crisp.
+    fn board_offset(&self, board: &game::Board) -> [f32; 2] {
+        [
+            ((self.projection[0] - board.width) * self.scale / 2.).floor() / self.scale,
+            ((self.projection[1] - board.height) * self.scale / 2.).floor() / self.scale
+        ]
+    }
+
+    /// Converts a window position in pixels from the top left, as mouse
+    /// events report it, to board coordinates. Ignores the screen shake, so
+    /// a still mouse points at a still spot.
+    pub fn window_to_board(&self, board: &game::Board, x: i32, y: i32) -> (f32, f32) {
+        let offset = self.board_offset(board);
+        (x as f32 / self.scale - offset[0], self.projection[1] - y as f32 / self.scale - offset[1])
+    }
 
     fn flush_draw_batch(&mut self, frame: &mut glium::Frame, board: &game::Board) {
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::Points);
         self.vertex_buffer.write(&self.shape);
+        let offset = self.board_offset(board);
         frame.draw(
             self.vertex_buffer.slice(..self.batch_index as usize).unwrap(), 
-            &indices, &self.program, &uniform!{
+            indices, &self.program, &uniform!{
                 projection: self.projection,
-                offset: [
-                    (self.projection[0] - board.width as f32) / 2.,
-                    (self.projection[1] - board.height as f32) / 2.
-                ]
+                offset: [offset[0] + self.shake[0], offset[1] + self.shake[1]],
+                pixel: 1. / self.scale
             },
-            &Default::default()).unwrap();
+            &glium::DrawParameters {
+                blend: glium::Blend::alpha_blending(),
+                // Keeps shaken and stray shapes out of the bars.
+                scissor: Some(self.viewport()),
+                .. Default::default()
+            }).unwrap();
         self.batch_index = 0;
     }
+}
 
-    pub fn draw(&mut self, mut frame: glium::Frame, board: &game::Board) {
-        self.vertex_buffer.write(&self.shape);
-        frame.clear_color(0.0, 0.0, 1.0, 1.0);
-        self.draw_rect(&mut frame, board, board.lhs_paddle.bound.clone());
-        self.draw_rect(&mut frame, board, board.rhs_paddle.bound.clone());
-        let lhs_goal_border_height = (board.height - board.lhs_goal_height) / 2.;
-        let rhs_goal_border_height = (board.height - board.rhs_goal_height) / 2.;
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: -BORDER_WIDTH, y: 0., 
-            width: BORDER_WIDTH, height: lhs_goal_border_height
-        });
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: -BORDER_WIDTH, y: board.height - lhs_goal_border_height, 
-            width: BORDER_WIDTH, height: lhs_goal_border_height
-        });
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: -BORDER_WIDTH, y: -BORDER_WIDTH,
-            width: board.width + 2. * BORDER_WIDTH, height: BORDER_WIDTH
-        });
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: -BORDER_WIDTH, y: board.height,
-            width: board.width + 2. * BORDER_WIDTH, height: BORDER_WIDTH
-        });
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: board.width, y: 0.,
-            width: BORDER_WIDTH, height: rhs_goal_border_height
-        });
-        self.draw_rect(&mut frame, board, game::Rect {
-            x: board.width, y: board.height - rhs_goal_border_height,
-            width: BORDER_WIDTH, height: rhs_goal_border_height
-        });
-        self.draw_number(&mut frame, board, board.lhs_score, board.width / 2. - SCORE_PADDING, SCORE_PADDING, false);
-        self.draw_number(&mut frame, board, board.rhs_score, board.width / 2. + SCORE_PADDING, SCORE_PADDING, true);
-        for i in 0..NUM_NET_SEGMENTS {
-            self.draw_rect(&mut frame, board, game::Rect {
-                x: board.width / 2., y: (i as f32 + 0.25) * board.height / (NUM_NET_SEGMENTS as f32),
-                width: NET_WIDTH, height: board.height / (2 * NUM_NET_SEGMENTS) as f32
-            });
-        }
-        self.draw_rect(&mut frame, board, board.ball.bound.clone());
-        self.flush_draw_batch(&mut frame, board);
-        frame.finish().unwrap();
+/// Adapts `BoardRenderer`'s point batches to `Canvas` for one frame.
+struct FrameCanvas<'a> {
+    renderer: &'a mut BoardRenderer,
+    frame: glium::Frame
+}
+
+impl<'a> Canvas for FrameCanvas<'a> {
+    fn draw_shape(&mut self, board: &game::Board, rect: game::Rect, shape: Shape, color: theme::Color) {
+        self.renderer.draw_shape(&mut self.frame, board, rect, shape, color);
+    }
+}
+
+impl Renderer for BoardRenderer {
+    /// Takes the window size in pixels, as resize events report it.
+    fn handle_frame_resize(&mut self, width: u32, height: u32) {
+        self.fit(width, height);
+    }
+
+    fn draw(&mut self, board: &game::Board, flow: &state::Match, effects: &effects::Effects) {
+        self.reload_changed_shaders();
+        let mut frame = self.dpy.draw();
+        // Also catches changes no resize event reports, like going
+        // fullscreen or moving to a screen with another HiDPI factor.
+        let (width, height) = frame.get_dimensions();
+        if (width, height) != self.framebuffer {
+            self.fit(width, height);
+        }
+        frame.clear_color(LETTERBOX_COLOR[0], LETTERBOX_COLOR[1], LETTERBOX_COLOR[2], LETTERBOX_COLOR[3]);
+        let background = board.config.graphics.theme().background;
+        frame.clear(Some(&self.viewport()), Some((background[0], background[1], background[2], background[3])),
+                    false, None, None);
+        let fps = self.fps.as_mut().map(|counter| counter.frame());
+        let (shake_x, shake_y) = effects.shake();
+        self.shake = [shake_x, shake_y];
+        let mut canvas = FrameCanvas { renderer: self, frame };
+        canvas.draw_scene(board, flow, effects, fps);
+        if let Some(log) = canvas.renderer.shader_error.clone() {
+            draw_log(&mut canvas, board, "Shader error", &log);
+        }
+        canvas.renderer.flush_draw_batch(&mut canvas.frame, board);
+        canvas.frame.finish().unwrap();
+    }
+}
+
+/// Fills the board with a titled block of text, wrapped to the board width
+/// and cut off at the bottom edge.
+fn draw_log<C: Canvas>(canvas: &mut C, board: &game::Board, title: &str, log: &str) {
+    let theme = board.config.graphics.theme();
+    let size = font::Size::Small;
+    let line_height = 1.5 * size.height();
+    let advance = (font::GLYPH_WIDTH + 1) as f32 * size.pixel();
+    let columns = ((board.width - 2. * line_height) / advance).max(1.) as usize;
+    canvas.draw_rect(board, game::Rect { x: 0., y: 0., width: board.width, height: board.height },
+                     [theme.background[0], theme.background[1], theme.background[2], 0.9]);
+    let mut lines = vec![title.to_string(), String::new()];
+    for line in log.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(columns) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    let max_lines = ((board.height / line_height) as usize).saturating_sub(1);
+    for (i, line) in lines.iter().take(max_lines).enumerate() {
+        let y = board.height - (i + 1) as f32 * line_height;
+        canvas.draw_text(board, line, (line_height, y), size, font::Align::Left, theme.text);
     }
 }
@@ -0,0 +1,42 @@
+/// FNV-1a, for the hashes that have to come out the same on every platform
+/// and build, unlike `std`'s randomly keyed `DefaultHasher`.
+pub struct Fnv {
+    hash: u64
+}
+
+impl Fnv {
+    pub fn new() -> Self {
+        Fnv { hash: 0xcbf29ce484222325 }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.hash = (self.hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Feeds `word` as its eight little-endian bytes.
+    pub fn write_u64(&mut self, word: u64) {
+        self.write_bytes(&word.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_values() {
+        assert_eq!(Fnv::new().finish(), 0xcbf29ce484222325);
+        let mut hash = Fnv::new();
+        hash.write_bytes(b"a");
+        assert_eq!(hash.finish(), 0xaf63dc4c8601ec8c);
+        let mut hash = Fnv::new();
+        hash.write_bytes(b"foobar");
+        assert_eq!(hash.finish(), 0x85944171f73967e8);
+    }
+}
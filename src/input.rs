@@ -0,0 +1,175 @@
+extern crate glium;
+
+use game;
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::fs::File;
+use glium::glutin::VirtualKeyCode;
+
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        &[$((stringify!($key), VirtualKeyCode::$key)),*]
+    }
+}
+
+/// Keys that may appear in a bindings file, by their glutin name.
+const KEY_NAMES: &[(&str, VirtualKeyCode)] = key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Insert, Home, Delete, End, PageDown, PageUp,
+    Left, Up, Right, Down, Back, Return, Space,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    Add, Apostrophe, Backslash, Comma, Equals, Grave, LAlt, LBracket, LControl, LShift,
+    Minus, Period, RAlt, RBracket, RControl, RShift, Semicolon, Slash, Subtract, Tab
+);
+
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES.iter()
+        .find(|&&(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|&(_, key)| key)
+}
+
+/// What a key does: either drive the board directly, or steer the menus and
+/// match flow around it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Board(game::Input),
+    Pause,
+    Confirm
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        match name {
+            "pause" => Some(Action::Pause),
+            "confirm" => Some(Action::Confirm),
+            _ => game::Input::from_name(name).map(Action::Board)
+        }
+    }
+}
+
+/// Maps keyboard keys to actions.
+pub struct Bindings {
+    keys: HashMap<VirtualKeyCode, Action>
+}
+
+impl Bindings {
+    /// Up/Down steer the left paddle, plus the ball debugging keys. Return or
+    /// Space confirms and P or Escape pauses.
+    pub fn single_player() -> Self {
+        Bindings::from_pairs(&[
+            (VirtualKeyCode::Up, Action::Board(game::Input::Up(game::Side::Lhs))),
+            (VirtualKeyCode::Down, Action::Board(game::Input::Down(game::Side::Lhs))),
+            (VirtualKeyCode::B, Action::Board(game::Input::ToggleBallSim)),
+            (VirtualKeyCode::W, Action::Board(game::Input::BallUp)),
+            (VirtualKeyCode::S, Action::Board(game::Input::BallDown)),
+            (VirtualKeyCode::Return, Action::Confirm),
+            (VirtualKeyCode::Space, Action::Confirm),
+            (VirtualKeyCode::P, Action::Pause),
+            (VirtualKeyCode::Escape, Action::Pause)
+        ])
+    }
+
+    /// W/S steer the left paddle and Up/Down steer the right one, with the
+    /// same menu keys as `single_player`.
+    pub fn two_player() -> Self {
+        Bindings::from_pairs(&[
+            (VirtualKeyCode::W, Action::Board(game::Input::Up(game::Side::Lhs))),
+            (VirtualKeyCode::S, Action::Board(game::Input::Down(game::Side::Lhs))),
+            (VirtualKeyCode::Up, Action::Board(game::Input::Up(game::Side::Rhs))),
+            (VirtualKeyCode::Down, Action::Board(game::Input::Down(game::Side::Rhs))),
+            (VirtualKeyCode::Return, Action::Confirm),
+            (VirtualKeyCode::Space, Action::Confirm),
+            (VirtualKeyCode::P, Action::Pause),
+            (VirtualKeyCode::Escape, Action::Pause)
+        ])
+    }
+
+    fn from_pairs(pairs: &[(VirtualKeyCode, Action)]) -> Self {
+        Bindings {
+            keys: pairs.iter().cloned().collect()
+        }
+    }
+
+    /// Reads a bindings file made of `<key> = <action>` lines, e.g.
+    /// `W = lhs_up` or `Escape = pause`. Blank lines and lines starting with `#` are ignored.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let mut keys = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("{}:{}: {}", path, i + 1, message);
+            let mut parts = line.splitn(2, '=').map(|part| part.trim());
+            let (key_name, action_name) = match (parts.next(), parts.next()) {
+                (Some(key_name), Some(action_name)) => (key_name, action_name),
+                _ => return Err(error("expected `<key> = <action>`".to_string()))
+            };
+            let key = key_from_name(key_name)
+                .ok_or_else(|| error(format!("unknown key `{}`", key_name)))?;
+            let action = Action::from_name(action_name)
+                .ok_or_else(|| error(format!("unknown action `{}`", action_name)))?;
+            if keys.insert(key, action).is_some() {
+                return Err(error(format!("key `{}` is bound twice", key_name)));
+            }
+        }
+        Ok(Bindings { keys })
+    }
+
+    pub fn lookup(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.keys.get(&key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn load_text(name: &str, text: &str) -> Result<Bindings, String> {
+        let path = env::temp_dir().join(format!("pong-test-{}-{}", process::id(), name));
+        fs::write(&path, text).unwrap();
+        let bindings = Bindings::load(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        bindings
+    }
+
+    fn load_error(name: &str, text: &str) -> String {
+        match load_text(name, text) {
+            Ok(_) => panic!("{:?} should not load", text),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn loads_a_bindings_file() {
+        let bindings = load_text("bindings", "# Left player\nw = lhs_up\nS = lhs_down\n\nEscape = pause\n").unwrap();
+        assert_eq!(bindings.lookup(VirtualKeyCode::W), Some(Action::Board(game::Input::Up(game::Side::Lhs))));
+        assert_eq!(bindings.lookup(VirtualKeyCode::S), Some(Action::Board(game::Input::Down(game::Side::Lhs))));
+        assert_eq!(bindings.lookup(VirtualKeyCode::Escape), Some(Action::Pause));
+        assert_eq!(bindings.lookup(VirtualKeyCode::Up), None);
+    }
+
+    #[test]
+    fn rejects_bad_bindings() {
+        assert!(load_error("no-equals", "W lhs_up\n").ends_with(":1: expected `<key> = <action>`"));
+        assert!(load_error("unknown-key", "Hyper = lhs_up\n").contains("unknown key `Hyper`"));
+        assert!(load_error("unknown-action", "W = jump\n").contains("unknown action `jump`"));
+        assert!(load_error("twice", "W = lhs_up\nW = lhs_down\n").contains(":2: key `W` is bound twice"));
+    }
+
+    #[test]
+    fn two_player_keys_steer_different_paddles() {
+        let bindings = Bindings::two_player();
+        assert_eq!(bindings.lookup(VirtualKeyCode::W), Some(Action::Board(game::Input::Up(game::Side::Lhs))));
+        assert_eq!(bindings.lookup(VirtualKeyCode::Up), Some(Action::Board(game::Input::Up(game::Side::Rhs))));
+    }
+}
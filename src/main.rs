@@ -1,36 +1,402 @@
+#[cfg(feature = "audio")]
 extern crate ears;
 #[macro_use]
 extern crate glium;
+mod ai;
+mod assets;
+mod audio;
+mod cli;
+mod config;
+mod effects;
+mod font;
 mod game;
+mod gamepad;
 mod graphics;
-use std::time::SystemTime;
+mod hash;
+mod input;
+mod net;
+mod replay;
+mod rng;
+mod soft;
+mod state;
+mod synth;
+mod theme;
+mod tui;
+use std::env;
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use glium::DisplayBuild;
+use graphics::Renderer;
 
-fn main() {
-    const BOARD_PADDING: u32 = 10;
-    let mut board = game::Board::new();
-    let width = board.width as u32 + 2 * BOARD_PADDING;
-    let height = board.height as u32 + 2 * BOARD_PADDING;
-    let dpy = glium::glutin::WindowBuilder::new()
-        .with_title("Pong")
-        .with_gl(
-            glium::glutin::GlRequest::Specific(
-                glium::glutin::Api::OpenGl,
-                (3, 3)))
-        .with_dimensions(width, height)
+// Longest frame we try to catch up on, so a stall doesn't trigger an
+// avalanche of simulation steps.
+const MAX_FRAME_TIME: f32 = 0.25;
+// Give up on a netplay peer after this many silent ticks.
+const NET_TIMEOUT_TICKS: u32 = 5 * game::TICK_RATE;
+// Evenly matched AIs can rally forever, so headless matches give up after an
+// hour of simulated play.
+const HEADLESS_MAX_TICKS: u64 = 60 * 60 * game::TICK_RATE as u64;
+
+/// Size of the window, or screenshot, showing the board with its padding.
+fn frame_size(board: &game::Board, scale: f32) -> (u32, u32) {
+    let padding = board.config.graphics.board_padding;
+    (((board.width + 2. * padding) * scale) as u32, ((board.height + 2. * padding) * scale) as u32)
+}
+
+fn open_window(board: &game::Board, assets: &assets::Assets, options: &cli::Options)
+               -> (glium::backend::glutin_backend::GlutinFacade, graphics::BoardRenderer) {
+    let (width, height) = frame_size(board, options.scale);
+    let dpy = graphics::window_builder(width, height, false)
         .build_glium()
         .unwrap();
-    let mut renderer = graphics::BoardRenderer::new(&dpy, width, height)
+    let padding = board.config.graphics.board_padding;
+    let mut renderer = graphics::BoardRenderer::new(&dpy, assets, board.width + 2. * padding, board.height + 2. * padding)
         .expect("Can't init board renderer.");
-    let mut last_update = SystemTime::now();
-    board.start_game(true);
+    renderer.set_show_fps(options.show_fps);
+    renderer.set_watch_shaders(options.dev);
+    renderer.set_integer_scale(options.integer_scale);
+    renderer.set_fullscreen(options.fullscreen);
+    (dpy, renderer)
+}
+
+/// Handles the keys that work on any screen: F11 or Alt+Enter toggle
+/// fullscreen. Returns whether `key` was used up.
+fn handle_display_key(renderer: &mut graphics::BoardRenderer, alt_held: &mut bool,
+                      state: glium::glutin::ElementState, key: glium::glutin::VirtualKeyCode) -> bool {
+    use glium::glutin::VirtualKeyCode;
+    let is_pressed = state == glium::glutin::ElementState::Pressed;
+    match key {
+        VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => {
+            *alt_held = is_pressed;
+            false
+        },
+        VirtualKeyCode::F11 => {
+            if is_pressed {
+                renderer.toggle_fullscreen();
+            }
+            true
+        },
+        VirtualKeyCode::Return if *alt_held => {
+            if is_pressed {
+                renderer.toggle_fullscreen();
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+fn time_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() ^ u64::from(elapsed.subsec_nanos()) << 32)
+        .unwrap_or(0)
+}
+
+/// How each side is named on screen and in results.
+fn player_names(lhs_controller: ai::ControllerKind, rhs_controller: ai::ControllerKind) -> (&'static str, &'static str) {
+    let is_ai = |kind| matches!(kind, ai::ControllerKind::Ai(_));
+    match (is_ai(lhs_controller), is_ai(rhs_controller)) {
+        (false, true) => ("Player", "AI"),
+        (true, false) => ("AI", "Player"),
+        (true, true) => ("Left AI", "Right AI"),
+        (false, false) => ("Left player", "Right player")
+    }
+}
+
+/// Simulates the match as fast as possible and prints how it ended.
+/// Returns the board as the match left it.
+fn run_headless(mut board: game::Board, mut player: Option<replay::ReplayPlayer>,
+                recording: &mut Option<(String, replay::Replay)>,
+                lhs_controller: ai::ControllerKind, rhs_controller: ai::ControllerKind) -> game::Board {
     while board.winner().is_none() {
-        renderer.draw(dpy.draw(), &board);
+        if player.as_ref().is_some_and(|player| player.is_finished(&board)) {
+            println!("Replay ended without a winner at {}-{}.", board.lhs_score, board.rhs_score);
+            return board;
+        }
+        if board.tick() >= HEADLESS_MAX_TICKS {
+            println!("No winner after {} ticks, stopped at {}-{}.", board.tick(), board.lhs_score, board.rhs_score);
+            replay::save_recording(recording, &board);
+            return board;
+        }
+        if let Some(ref mut player) = player {
+            player.apply(&mut board);
+        }
+        board.update(game::TICK_DT);
+        board.drain_events();
+    }
+    replay::save_recording(recording, &board);
+    let (lhs_name, rhs_name) = player_names(lhs_controller, rhs_controller);
+    println!("{} won {}-{} after {} ticks.", if board.winner().unwrap() { lhs_name } else { rhs_name },
+             board.lhs_score, board.rhs_score, board.tick());
+    board
+}
+
+/// Aims the left paddle at the mouse's last position this frame, `y` on the
+/// board, unless that's where it was last aimed.
+fn mouse_aim(y: Option<f32>, last_aim: &mut Option<i16>) -> Option<(input::Action, bool)> {
+    let y = y?.round().clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+    if *last_aim == Some(y) {
+        return None;
+    }
+    *last_aim = Some(y);
+    Some((input::Action::Board(game::Input::Aim(game::Side::Lhs, y)), true))
+}
+
+/// Seconds since `last_frame`, which is reset to now.
+fn frame_time(last_frame: &mut Instant) -> f32 {
+    let elapsed = last_frame.elapsed();
+    *last_frame = Instant::now();
+    (elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1000000000.).min(MAX_FRAME_TIME)
+}
+
+fn run_netplay<T: net::Transport>(mut session: net::Session<T>, bindings: &input::Bindings,
+                                  options: &cli::Options, assets: &assets::Assets) {
+    let (dpy, mut renderer) = open_window(session.board(), assets, options);
+    let mut audio = audio::open(assets, &session.board().config, options.mute);
+    let mut effects = effects::Effects::new();
+    let mut last_aim = None;
+    let mut alt_held = false;
+    let mut last_frame = Instant::now();
+    let mut accumulator = 0.;
+    let mut prev_board = session.board().clone();
+    let mut warned_desync = false;
+    let mut gamepads = gamepad::Gamepads::new(gamepad::open(&session.board().config.gamepad),
+                                              &session.board().config.gamepad, &[session.local_side()]);
+    let mut flow = state::Match::playing();
+    match session.local_side() {
+        game::Side::Lhs => flow.set_names("You", "Opponent"),
+        game::Side::Rhs => flow.set_names("Opponent", "You")
+    }
+    println!("Waiting for the other player...");
+    while session.board().winner().is_none() {
+        renderer.draw(&prev_board.lerp(session.board(), accumulator / game::TICK_DT), &flow, &effects);
+        let mut actions = Vec::new();
+        let mut mouse_y = None;
         for ev in dpy.poll_events() {
             match ev {
                 glium::glutin::Event::Closed => return,
                 glium::glutin::Event::KeyboardInput(state, _, Some(key)) => {
-                    board.handle_input(key, state != glium::glutin::ElementState::Released);
+                    if handle_display_key(&mut renderer, &mut alt_held, state, key) {
+                        continue;
+                    }
+                    if let Some(action) = bindings.lookup(key) {
+                        actions.push((action, state != glium::glutin::ElementState::Released));
+                    }
+                }
+                glium::glutin::Event::MouseMoved(x, y) if options.mouse => {
+                    mouse_y = Some(renderer.window_to_board(session.board(), x, y).1);
+                }
+                glium::glutin::Event::Resized(width, height) => {
+                    renderer.handle_frame_resize(width, height);
+                }
+                _ => ()
+            }
+        }
+        actions.extend(mouse_aim(mouse_y, &mut last_aim));
+        actions.extend(gamepads.poll());
+        for (action, is_pressed) in actions {
+            if let input::Action::Board(input) = action {
+                session.add_input(input, is_pressed);
+            }
+        }
+        accumulator += frame_time(&mut last_frame);
+        while accumulator >= game::TICK_DT {
+            accumulator -= game::TICK_DT;
+            session.poll();
+            if session.is_mismatched() {
+                println!("The other player's match settings differ from yours; use the same --config and --win-score.");
+                return;
+            }
+            if session.is_connected() && session.polls_since_contact() > NET_TIMEOUT_TICKS {
+                println!("Lost connection to the other player.");
+                return;
+            }
+            let before = session.board().clone();
+            if session.advance() {
+                prev_board = before;
+            }
+            for event in session.drain_events() {
+                audio.handle_event(session.board(), &event);
+                effects.handle_event(session.board(), &event);
+            }
+            effects.update(session.board(), game::TICK_DT);
+        }
+        if session.is_desynced() && !warned_desync {
+            println!("Warning: the game has gone out of sync with the other player.");
+            warned_desync = true;
+        }
+    }
+    let lhs_won = session.board().winner().unwrap();
+    let local_won = lhs_won == (session.local_side() == game::Side::Lhs);
+    println!("{}", if local_won { "You won!" } else { "You lost!" });
+}
+
+fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        },
+        Ok(cli::Command::Run(options)) => *options,
+        Err(e) => {
+            println!("{}\nRun `pong --help` for usage.", e);
+            process::exit(2);
+        }
+    };
+    let mut config = match options.config_path {
+        Some(ref path) => config::GameConfig::load(path).unwrap_or_else(|e| {
+            println!("Invalid config: {}", e);
+            process::exit(1);
+        }),
+        None => config::GameConfig::default()
+    };
+    if let Some(win_score) = options.win_score {
+        config.win_score = win_score;
+    }
+    let mut assets = assets::Assets::new(options.assets_dir.as_deref());
+    if options.dev && !assets.has_dirs() {
+        println!("No --assets or {} given; watching shaders under the current directory.", assets::ASSETS_ENV);
+        assets = assets::Assets::new(Some("."));
+    }
+    let mut player = options.replay_path.as_ref().map(|path| {
+        let replay = replay::Replay::load(path).unwrap_or_else(|e| {
+            println!("Can't load replay {}: {}", path, e);
+            process::exit(1);
+        });
+        if let Err(e) = replay.apply_settings(&mut config) {
+            println!("Can't play replay {}: {}", path, e);
+            process::exit(1);
+        }
+        replay::ReplayPlayer::new(replay)
+    });
+    let (lhs_start, serve_seed, lhs_controller, rhs_controller) = match player {
+        Some(ref player) => {
+            let replay = player.replay();
+            (replay.lhs_start, replay.serve_seed, replay.lhs_controller, replay.rhs_controller)
+        },
+        None => {
+            let seed = options.seed.unwrap_or_else(time_seed);
+            if options.headless && options.seed.is_none() {
+                println!("Seed: {}", seed);
+            }
+            let mut rng = rng::Rng::new(seed);
+            let lhs_start = match options.serve {
+                cli::Serve::Left => true,
+                cli::Serve::Right => false,
+                cli::Serve::Random => rng.next_f32() < 0.5
+            };
+            let ai = ai::ControllerKind::Ai(options.difficulty);
+            let (lhs, rhs) = match options.mode {
+                cli::Mode::TwoPlayer => (ai::ControllerKind::Human, ai::ControllerKind::Human),
+                cli::Mode::AiVsAi => (ai, ai),
+                _ => (ai::ControllerKind::Human, ai)
+            };
+            (lhs_start, Some(rng.next_u64()), lhs, rhs)
+        }
+    };
+    let mut recording = options.record_path.clone().map(|path| {
+        (path, replay::Replay::new(lhs_start, serve_seed, lhs_controller, rhs_controller, &config))
+    });
+
+    let bindings = match options.bindings_path {
+        Some(ref path) => input::Bindings::load(path).unwrap_or_else(|e| {
+            println!("Can't load bindings: {}", e);
+            process::exit(1);
+        }),
+        None if options.mode == cli::Mode::TwoPlayer => input::Bindings::two_player(),
+        None => input::Bindings::single_player()
+    };
+
+    if options.host_port.is_some() || options.join_addr.is_some() {
+        let mut board = game::Board::new(config);
+        board.set_controller(game::Side::Lhs, game::Controller::Human);
+        board.set_controller(game::Side::Rhs, game::Controller::Human);
+        board.start_game(true);
+        let (transport, side) = match options.join_addr {
+            Some(ref addr) => (net::UdpTransport::join(addr), game::Side::Rhs),
+            None => (net::UdpTransport::host(options.host_port.unwrap()), game::Side::Lhs)
+        };
+        let transport = transport.unwrap_or_else(|e| {
+            println!("Can't open network socket: {}", e);
+            process::exit(1);
+        });
+        run_netplay(net::Session::new(transport, side, board), &bindings, &options, &assets);
+        return;
+    }
+
+    let mut board = game::Board::new(config);
+    board.set_controller(game::Side::Lhs, lhs_controller.controller());
+    board.set_controller(game::Side::Rhs, rhs_controller.controller());
+    if let Some(seed) = serve_seed {
+        board.set_serve_seed(seed);
+    }
+    board.start_game(lhs_start);
+    let mut flow = if player.is_some() { state::Match::for_replay() } else { state::Match::new(lhs_start) };
+    let (lhs_name, rhs_name) = player_names(lhs_controller, rhs_controller);
+    flow.set_names(lhs_name, rhs_name);
+    if options.headless || options.screenshot_path.is_some() {
+        if options.headless {
+            board = run_headless(board, player, &mut recording, lhs_controller, rhs_controller);
+            flow.skip_to_board(&board);
+        }
+        if let Some(ref path) = options.screenshot_path {
+            let (width, height) = frame_size(&board, options.scale);
+            let mut renderer = soft::SoftRenderer::new(width, height, options.scale);
+            renderer.draw(&board, &flow, &effects::Effects::new());
+            if let Err(e) = renderer.save_png(path) {
+                println!("Failed to save screenshot to {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if options.tui {
+        match tui::run(board, flow, player, &mut recording, options.mode == cli::Mode::TwoPlayer) {
+            Ok(board) => replay::save_recording(&mut recording, &board),
+            Err(e) => {
+                println!("Can't play in the terminal: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    let (dpy, mut renderer) = open_window(&board, &assets, &options);
+    let mut audio = audio::open(&assets, &board.config, options.mute);
+    let mut effects = effects::Effects::new();
+    let human_sides: Vec<_> = [(game::Side::Lhs, lhs_controller), (game::Side::Rhs, rhs_controller)].iter()
+        .filter(|&&(_, kind)| kind == ai::ControllerKind::Human)
+        .map(|&(side, _)| side)
+        .collect();
+    let mut gamepads = gamepad::Gamepads::new(gamepad::open(&board.config.gamepad), &board.config.gamepad, &human_sides);
+    let mut last_aim = None;
+    let mut alt_held = false;
+    let mut last_frame = Instant::now();
+    let mut accumulator = 0.;
+    let mut prev_board = board.clone();
+    while !flow.should_quit() {
+        if board.winner().is_none() && player.as_ref().is_some_and(|player| player.is_finished(&board)) {
+            println!("Replay ended without a winner.");
+            return;
+        }
+        renderer.draw(&prev_board.lerp(&board, accumulator / game::TICK_DT), &flow, &effects);
+        let mut actions = Vec::new();
+        let mut mouse_y = None;
+        for ev in dpy.poll_events() {
+            match ev {
+                glium::glutin::Event::Closed => {
+                    replay::save_recording(&mut recording, &board);
+                    return;
+                },
+                glium::glutin::Event::KeyboardInput(state, _, Some(key)) => {
+                    if handle_display_key(&mut renderer, &mut alt_held, state, key) {
+                        continue;
+                    }
+                    if let Some(action) = bindings.lookup(key) {
+                        actions.push((action, state != glium::glutin::ElementState::Released));
+                    }
+                }
+                glium::glutin::Event::MouseMoved(x, y) if options.mouse => {
+                    mouse_y = Some(renderer.window_to_board(&board, x, y).1);
                 }
                 glium::glutin::Event::Resized(width, height) => {
                     renderer.handle_frame_resize(width, height);
@@ -38,9 +404,40 @@ fn main() {
                 _ => ()
             }
         }
-        let dt: f32 = last_update.elapsed().unwrap().subsec_nanos() as f32 / 1000000000.;
-        last_update = SystemTime::now();
-        board.update(dt);
+        actions.extend(mouse_aim(mouse_y, &mut last_aim));
+        actions.extend(gamepads.poll());
+        for (action, is_pressed) in actions {
+            if let Some(input) = flow.handle_action(&mut board, action, is_pressed) {
+                if let Some((_, ref mut replay)) = recording {
+                    replay.record(board.tick(), input, is_pressed);
+                }
+            }
+        }
+        accumulator += frame_time(&mut last_frame);
+        while accumulator >= game::TICK_DT {
+            accumulator -= game::TICK_DT;
+            prev_board = board.clone();
+            if flow.tick(&board) {
+                if let Some(ref mut player) = player {
+                    player.apply(&mut board);
+                }
+                board.update(game::TICK_DT);
+                for event in board.drain_events() {
+                    audio.handle_event(&board, &event);
+                    effects.handle_event(&board, &event);
+                    flow.handle_event(&event);
+                }
+            }
+            if let Some(seconds_left) = flow.take_countdown_beep() {
+                audio.countdown(&board, seconds_left);
+            }
+            // Effects play out while the board holds after a goal.
+            effects.update(&board, game::TICK_DT);
+        }
+        // A recording covers a single match, so rematches aren't recorded.
+        if board.winner().is_some() {
+            replay::save_recording(&mut recording, &board);
+        }
     }
-    println!("{} won!", if board.winner().unwrap() { "Player" } else { "AI" });
+    replay::save_recording(&mut recording, &board);
 }
@@ -0,0 +1,693 @@
+use game;
+use game::{Board, Input, Side};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+const MAGIC: &[u8; 4] = b"PONG";
+const PROTOCOL_VERSION: u8 = 3;
+/// How far the local simulation may run ahead of the last tick the peer has
+/// confirmed before it stalls and waits.
+const MAX_ROLLBACK: u64 = 30;
+/// Confirmed states whose tick is a multiple of this get their checksum
+/// exchanged with the peer to detect desyncs.
+const CHECKSUM_INTERVAL: u64 = 16;
+const MAX_CHECKSUMS: usize = 256;
+const MAX_EVENTS_PER_PACKET: usize = 64;
+/// A tick, an input code, and the input's value or its press flag.
+const EVENT_SIZE: usize = 11;
+
+/// Unreliable datagram link to the other player.
+pub trait Transport {
+    fn send(&mut self, packet: &[u8]);
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>
+}
+
+impl UdpTransport {
+    /// Listens on `port`; the peer is whoever sends the first packet.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, peer: None })
+    }
+
+    pub fn join(addr: &str) -> io::Result<Self> {
+        let peer = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("can't resolve {}", addr)))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, peer: Some(peer) })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) {
+        if let Some(peer) = self.peer {
+            // Dropped packets are resent as part of the next one anyway.
+            let _ = self.socket.send_to(packet, peer);
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let mut buf = [0u8; 2048];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    if self.peer.is_none() {
+                        self.peer = Some(from);
+                    }
+                    if self.peer == Some(from) {
+                        return Some(buf[..len].to_vec());
+                    }
+                },
+                Err(_) => return None
+            }
+        }
+    }
+}
+
+type TimedInput = (u64, Input, bool);
+
+struct Packet {
+    /// `GameConfig::simulation_hash` and the win score, which must match
+    /// ours before any input is taken.
+    config_hash: u64,
+    win_score: u32,
+    tick: u64,
+    ack: u32,
+    first_seq: u32,
+    events: Vec<TimedInput>,
+    checksum: Option<(u64, u64)>
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(52 + self.events.len() * EVENT_SIZE);
+        buf.extend_from_slice(MAGIC);
+        buf.push(PROTOCOL_VERSION);
+        buf.extend_from_slice(&self.config_hash.to_be_bytes());
+        buf.extend_from_slice(&self.win_score.to_be_bytes());
+        buf.extend_from_slice(&self.tick.to_be_bytes());
+        buf.extend_from_slice(&self.ack.to_be_bytes());
+        buf.extend_from_slice(&self.first_seq.to_be_bytes());
+        buf.extend_from_slice(&(self.events.len() as u16).to_be_bytes());
+        for &(tick, input, is_pressed) in self.events.iter() {
+            buf.extend_from_slice(&tick.to_be_bytes());
+            buf.push(input.code());
+            let value = input.value().unwrap_or(is_pressed as i16);
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+        let (checksum_tick, checksum) = self.checksum.unwrap_or((u64::MAX, 0));
+        buf.extend_from_slice(&checksum_tick.to_be_bytes());
+        buf.extend_from_slice(&checksum.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<Packet> {
+        struct Reader<'a>(&'a [u8]);
+        impl<'a> Reader<'a> {
+            fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+                if self.0.len() < len {
+                    return None;
+                }
+                let (head, tail) = self.0.split_at(len);
+                self.0 = tail;
+                Some(head)
+            }
+            fn u8(&mut self) -> Option<u8> {
+                self.take(1).map(|bytes| bytes[0])
+            }
+            fn u16(&mut self) -> Option<u16> {
+                let mut bytes = [0; 2];
+                bytes.copy_from_slice(self.take(2)?);
+                Some(u16::from_be_bytes(bytes))
+            }
+            fn u32(&mut self) -> Option<u32> {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(self.take(4)?);
+                Some(u32::from_be_bytes(bytes))
+            }
+            fn u64(&mut self) -> Option<u64> {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                Some(u64::from_be_bytes(bytes))
+            }
+        }
+
+        let mut reader = Reader(buf);
+        if reader.take(4)? != MAGIC || reader.u8()? != PROTOCOL_VERSION {
+            return None;
+        }
+        let config_hash = reader.u64()?;
+        let win_score = reader.u32()?;
+        let tick = reader.u64()?;
+        let ack = reader.u32()?;
+        let first_seq = reader.u32()?;
+        let count = reader.u16()?;
+        let mut events = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let tick = reader.u64()?;
+            let input = Input::from_code(reader.u8()?)?;
+            let value = reader.u16()? as i16;
+            let (input, is_pressed) = input.with_value(value);
+            events.push((tick, input, is_pressed));
+        }
+        let checksum_tick = reader.u64()?;
+        let checksum = reader.u64()?;
+        Some(Packet {
+            config_hash,
+            win_score,
+            tick,
+            ack,
+            first_seq,
+            events,
+            checksum: if checksum_tick == u64::MAX { None } else { Some((checksum_tick, checksum)) }
+        })
+    }
+}
+
+/// Rollback netplay session for one side of a two-player match.
+///
+/// Local inputs take effect immediately while the peer's are predicted to
+/// stay as they were. When the peer's real inputs arrive for a tick that was
+/// already simulated, the board is rewound to a saved state from that tick
+/// and re-simulated with the corrected inputs.
+pub struct Session<T: Transport> {
+    transport: T,
+    local_side: Side,
+    board: Board,
+    config_hash: u64,
+    /// `history[i]` is the board at tick `history_base + i`, before that
+    /// tick's inputs were applied.
+    history: VecDeque<Board>,
+    history_base: u64,
+    pending: Vec<(Input, bool)>,
+    local_inputs: Vec<TimedInput>,
+    remote_inputs: Vec<TimedInput>,
+    /// How many of `local_inputs` the peer has acknowledged.
+    remote_ack: usize,
+    /// The peer has sent us every input for the ticks before this one.
+    remote_tick: u64,
+    /// The peer has sent every input for the ticks before this one.
+    confirmed_tick: u64,
+    local_checksums: VecDeque<(u64, u64)>,
+    remote_checksums: VecDeque<(u64, u64)>,
+    connected: bool,
+    /// The peer's settings differ from ours, so nothing it sends is used.
+    mismatched: bool,
+    polls_since_contact: u32,
+    desynced: bool,
+    events: Vec<game::Event>
+}
+
+impl<T: Transport> Session<T> {
+    /// Both peers must start from identical boards.
+    pub fn new(transport: T, local_side: Side, board: Board) -> Self {
+        let history_base = board.tick();
+        Session {
+            transport,
+            local_side,
+            config_hash: board.config.simulation_hash(),
+            board,
+            history: VecDeque::new(),
+            history_base,
+            pending: Vec::new(),
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            remote_ack: 0,
+            remote_tick: history_base,
+            confirmed_tick: history_base,
+            local_checksums: VecDeque::new(),
+            remote_checksums: VecDeque::new(),
+            connected: false,
+            mismatched: false,
+            polls_since_contact: 0,
+            desynced: false,
+            events: Vec::new()
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn local_side(&self) -> Side {
+        self.local_side
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn is_desynced(&self) -> bool {
+        self.desynced
+    }
+
+    /// Whether the peer is playing with different settings. The match never
+    /// starts when they do.
+    pub fn is_mismatched(&self) -> bool {
+        self.mismatched
+    }
+
+    /// Number of `poll` calls since the last packet from the peer.
+    pub fn polls_since_contact(&self) -> u32 {
+        self.polls_since_contact
+    }
+
+    /// Queues a local input for the next tick. Only paddle inputs are sent,
+    /// and they always steer the local paddle.
+    pub fn add_input(&mut self, input: Input, is_pressed: bool) {
+        let input = match input {
+            Input::Up(_) => Input::Up(self.local_side),
+            Input::Down(_) => Input::Down(self.local_side),
+            Input::Stick(_, value) => Input::Stick(self.local_side, value),
+            Input::Aim(_, y) => Input::Aim(self.local_side, y),
+            _ => return
+        };
+        self.pending.push((input, is_pressed));
+    }
+
+    /// Takes the board events from ticks simulated for the first time.
+    /// Events from re-simulated ticks are dropped.
+    pub fn drain_events(&mut self) -> Vec<game::Event> {
+        ::std::mem::take(&mut self.events)
+    }
+
+    /// Simulates one tick, unless the peer hasn't connected yet or we are
+    /// too far ahead of it. Returns whether the board advanced.
+    pub fn advance(&mut self) -> bool {
+        let tick = self.board.tick();
+        if !self.connected || tick >= self.remote_tick + MAX_ROLLBACK {
+            return false;
+        }
+        for (input, is_pressed) in self.pending.drain(..) {
+            self.local_inputs.push((tick, input, is_pressed));
+        }
+        self.step();
+        let events = self.board.drain_events();
+        self.events.extend(events);
+        true
+    }
+
+    fn step(&mut self) {
+        let tick = self.board.tick();
+        self.history.push_back(self.board.clone());
+        let (lhs_inputs, rhs_inputs) = match self.local_side {
+            Side::Lhs => (&self.local_inputs, &self.remote_inputs),
+            Side::Rhs => (&self.remote_inputs, &self.local_inputs)
+        };
+        // Apply the left side's inputs first so both peers agree on order.
+        for inputs in [lhs_inputs, rhs_inputs].iter() {
+            let start = inputs.partition_point(|&(t, _, _)| t < tick);
+            for &(_, input, is_pressed) in inputs[start..].iter().take_while(|&&(t, _, _)| t == tick) {
+                self.board.handle_input(input, is_pressed);
+            }
+        }
+        self.board.update(game::TICK_DT);
+    }
+
+    /// Exchanges packets with the peer, rolling back if its inputs disagree
+    /// with what was predicted.
+    pub fn poll(&mut self) {
+        self.polls_since_contact = self.polls_since_contact.saturating_add(1);
+        let mut rollback_tick = None;
+        while let Some(buf) = self.transport.recv() {
+            let packet = match Packet::decode(&buf) {
+                Some(packet) => packet,
+                None => continue
+            };
+            if packet.config_hash != self.config_hash || packet.win_score != self.board.config.win_score {
+                self.mismatched = true;
+                continue;
+            }
+            let first_seq = packet.first_seq as usize;
+            let known = self.remote_inputs.len();
+            if first_seq > known {
+                // A gap; everything from `known` on will be resent.
+                continue;
+            }
+            let new_events = &packet.events[(known - first_seq).min(packet.events.len())..];
+            if !self.accepts(new_events) {
+                continue;
+            }
+            self.connected = true;
+            self.polls_since_contact = 0;
+            // The peer can't have seen more inputs than we've sent.
+            self.remote_ack = self.remote_ack.max((packet.ack as usize).min(self.local_inputs.len()));
+            for &event in new_events.iter() {
+                if event.0 < self.board.tick() {
+                    rollback_tick = Some(rollback_tick.map_or(event.0, |t: u64| t.min(event.0)));
+                }
+                self.remote_inputs.push(event);
+            }
+            self.remote_tick = self.remote_tick.max(packet.tick);
+            if let Some(checksum) = packet.checksum {
+                self.remote_checksums.push_back(checksum);
+                if self.remote_checksums.len() > MAX_CHECKSUMS {
+                    self.remote_checksums.pop_front();
+                }
+            }
+        }
+        if let Some(tick) = rollback_tick {
+            self.rollback(tick);
+        }
+        self.confirm();
+        self.send();
+    }
+
+    /// Whether `events` could have come from an honest peer: inputs for its
+    /// own paddle only, in tick order, and none for a tick it already said
+    /// was complete. A packet with anything else is dropped whole.
+    fn accepts(&self, events: &[TimedInput]) -> bool {
+        let remote_side = match self.local_side {
+            Side::Lhs => Side::Rhs,
+            Side::Rhs => Side::Lhs
+        };
+        let mut earliest = self.remote_tick;
+        for &(tick, input, _) in events.iter() {
+            let side = match input {
+                Input::Up(side) | Input::Down(side) | Input::Stick(side, _) | Input::Aim(side, _) => side,
+                _ => return false
+            };
+            if side != remote_side || tick < earliest {
+                return false;
+            }
+            earliest = tick;
+        }
+        true
+    }
+
+    fn rollback(&mut self, tick: u64) {
+        let end = self.board.tick();
+        let index = (tick - self.history_base) as usize;
+        self.board = self.history[index].clone();
+        self.history.truncate(index);
+        while self.board.tick() < end {
+            self.step();
+            self.board.drain_events();
+        }
+    }
+
+    /// Records checksums of states that can no longer be rolled back and
+    /// forgets their saved boards.
+    fn confirm(&mut self) {
+        let confirmed = self.remote_tick.min(self.board.tick());
+        while self.confirmed_tick < confirmed {
+            self.confirmed_tick += 1;
+            if self.confirmed_tick.is_multiple_of(CHECKSUM_INTERVAL) {
+                let index = (self.confirmed_tick - self.history_base) as usize;
+                let checksum = self.history.get(index).unwrap_or(&self.board).checksum();
+                self.local_checksums.push_back((self.confirmed_tick, checksum));
+                if self.local_checksums.len() > MAX_CHECKSUMS {
+                    self.local_checksums.pop_front();
+                }
+            }
+        }
+        while self.history_base < self.confirmed_tick && !self.history.is_empty() {
+            self.history.pop_front();
+            self.history_base += 1;
+        }
+        for &(tick, remote) in self.remote_checksums.iter() {
+            let local = self.local_checksums.iter().find(|&&(t, _)| t == tick);
+            if let Some(&(_, local)) = local {
+                if local != remote {
+                    self.desynced = true;
+                }
+            }
+        }
+        let newest_local = self.local_checksums.back().map_or(0, |&(t, _)| t);
+        self.remote_checksums.retain(|&(t, _)| t > newest_local);
+    }
+
+    fn send(&mut self) {
+        let first = self.remote_ack;
+        let last = self.local_inputs.len().min(first + MAX_EVENTS_PER_PACKET);
+        // Inputs for the current tick may still be added by `advance`, and
+        // anything past `last` has to wait for the next packet.
+        let tick = self.local_inputs.get(last).map_or(self.board.tick(), |&(tick, _, _)| tick);
+        let packet = Packet {
+            config_hash: self.config_hash,
+            win_score: self.board.config.win_score,
+            tick,
+            ack: self.remote_inputs.len() as u32,
+            first_seq: first as u32,
+            events: self.local_inputs[first..last].to_vec(),
+            checksum: self.local_checksums.back().cloned()
+        };
+        self.transport.send(&packet.encode());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use rng::Rng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Link {
+        clock: u64,
+        loss: f32,
+        delay: u64,
+        jitter: u64,
+        rng: Rng,
+        in_flight: [Vec<(u64, Vec<u8>)>; 2]
+    }
+
+    /// In-process transport with simulated packet loss, delay and reordering.
+    /// Time only moves when `LoopbackTransport::advance_clock` is called.
+    struct LoopbackTransport {
+        link: Rc<RefCell<Link>>,
+        index: usize
+    }
+
+    impl LoopbackTransport {
+        /// Creates both ends of a link. Each packet is dropped with probability
+        /// `loss` and otherwise arrives `delay` to `delay + jitter` ticks later.
+        fn pair(loss: f32, delay: u64, jitter: u64, seed: u64) -> (Self, Self) {
+            let link = Rc::new(RefCell::new(Link {
+                clock: 0,
+                loss,
+                delay,
+                jitter,
+                rng: Rng::new(seed),
+                in_flight: [Vec::new(), Vec::new()]
+            }));
+            (LoopbackTransport { link: link.clone(), index: 0 }, LoopbackTransport { link, index: 1 })
+        }
+
+        fn advance_clock(&self) {
+            self.link.borrow_mut().clock += 1;
+        }
+
+        fn set_loss(&self, loss: f32) {
+            self.link.borrow_mut().loss = loss;
+        }
+    }
+
+    impl Transport for LoopbackTransport {
+        fn send(&mut self, packet: &[u8]) {
+            let mut link = self.link.borrow_mut();
+            if link.rng.next_f32() < link.loss {
+                return;
+            }
+            let jitter = (link.rng.next_f32() * (link.jitter + 1) as f32) as u64;
+            let arrival = link.clock + link.delay + jitter;
+            link.in_flight[1 - self.index].push((arrival, packet.to_vec()));
+        }
+
+        fn recv(&mut self) -> Option<Vec<u8>> {
+            let mut link = self.link.borrow_mut();
+            let clock = link.clock;
+            let queue = &mut link.in_flight[self.index];
+            let due = queue.iter().position(|&(arrival, _)| arrival <= clock)?;
+            Some(queue.remove(due).1)
+        }
+    }
+
+    /// Plays two sessions against each other over a lossy in-process link
+    /// with scripted random inputs, until both have confirmed `ticks`.
+    fn play(config: GameConfig, ticks: u64, loss: f32, delay: u64, seed: u64) -> [Session<LoopbackTransport>; 2] {
+        let (lhs_link, rhs_link) = LoopbackTransport::pair(loss, delay, delay / 2, seed);
+        let mut board = Board::new(config);
+        board.set_controller(Side::Lhs, game::Controller::Human);
+        board.set_controller(Side::Rhs, game::Controller::Human);
+        board.start_game(true);
+        let mut sessions = [
+            Session::new(lhs_link, Side::Lhs, board.clone()),
+            Session::new(rhs_link, Side::Rhs, board)
+        ];
+        let mut rng = Rng::new(seed.wrapping_add(1));
+        // Keep going after the last input until both sides have caught up.
+        for clock in 1.. {
+            assert!(clock <= ticks * 4 + 1000, "sessions never converged");
+            sessions[0].transport.advance_clock();
+            if clock == ticks {
+                sessions[0].transport.set_loss(0.);
+            }
+            for session in sessions.iter_mut() {
+                session.poll();
+                if session.board().tick() < ticks {
+                    if rng.next_f32() < 0.05 {
+                        let input = match rng.next_f32() {
+                            r if r < 0.4 => Input::Up(Side::Lhs),
+                            r if r < 0.8 => Input::Down(Side::Lhs),
+                            r if r < 0.9 => Input::Stick(Side::Lhs, ((2. * rng.next_f32() - 1.) * f32::from(game::STICK_MAX)) as i16),
+                            _ => Input::Aim(Side::Lhs, (rng.next_f32() * session.board().height) as i16)
+                        };
+                        session.add_input(input, rng.next_f32() < 0.6);
+                    }
+                    session.advance();
+                }
+            }
+            assert!(!sessions.iter().any(|session| session.is_desynced()),
+                    "checksums diverged by tick {}", sessions[0].confirmed_tick);
+            if sessions.iter().all(|session| session.board().tick() == ticks && session.confirmed_tick == ticks) {
+                break;
+            }
+        }
+        sessions
+    }
+
+    fn assert_in_sync(config: GameConfig, loss: f32, delay: u64, seed: u64) {
+        let ticks = 10 * u64::from(game::TICK_RATE);
+        let sessions = play(config, ticks, loss, delay, seed);
+        let (lhs, rhs) = (sessions[0].board(), sessions[1].board());
+        assert_eq!(lhs.tick(), ticks);
+        assert_eq!(lhs.checksum(), rhs.checksum(),
+                   "final states differ with loss {}, delay {} and seed {}", loss, delay, seed);
+    }
+
+    #[test]
+    fn stays_in_sync_on_a_perfect_link() {
+        assert_in_sync(GameConfig::default(), 0., 0, 1);
+    }
+
+    #[test]
+    fn stays_in_sync_under_loss_and_delay() {
+        for &(loss, delay, seed) in [(0.1, 4, 1), (0.3, 2, 2), (0.05, 12, 3), (0.5, 6, 4), (0.2, 20, 5)].iter() {
+            assert_in_sync(GameConfig::default(), loss, delay, seed);
+        }
+    }
+
+    #[test]
+    fn stays_in_sync_with_power_ups() {
+        let mut config = GameConfig::default();
+        config.power_ups.enabled = true;
+        config.power_ups.spawn_interval = 1.;
+        for seed in 6..9 {
+            assert_in_sync(config.clone(), 0.15, 5, seed);
+        }
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let packet = Packet {
+            config_hash: 0x0123_4567_89ab_cdef,
+            win_score: 11,
+            tick: 300,
+            ack: 12,
+            first_seq: 7,
+            events: vec![(298, Input::Up(Side::Rhs), true), (299, Input::Stick(Side::Rhs, -1234), true),
+                         (299, Input::Aim(Side::Rhs, 150), true)],
+            checksum: Some((288, 0xdead_beef))
+        };
+        let decoded = Packet::decode(&packet.encode()).unwrap();
+        assert_eq!((decoded.config_hash, decoded.win_score), (0x0123_4567_89ab_cdef, 11));
+        assert_eq!((decoded.tick, decoded.ack, decoded.first_seq), (300, 12, 7));
+        assert_eq!(decoded.events, packet.events);
+        assert_eq!(decoded.checksum, packet.checksum);
+        assert!(Packet::decode(b"PONG").is_none());
+    }
+
+    /// A left-hand session, and the other end of its link to send it
+    /// hand-made packets.
+    fn lone_session() -> (Session<LoopbackTransport>, LoopbackTransport) {
+        let (link, peer) = LoopbackTransport::pair(0., 0, 0, 1);
+        let mut board = Board::new(GameConfig::default());
+        board.set_controller(Side::Lhs, game::Controller::Human);
+        board.set_controller(Side::Rhs, game::Controller::Human);
+        board.start_game(true);
+        (Session::new(link, Side::Lhs, board), peer)
+    }
+
+    fn packet_for(session: &Session<LoopbackTransport>, tick: u64, ack: u32, events: Vec<TimedInput>) -> Packet {
+        Packet {
+            config_hash: session.config_hash,
+            win_score: session.board.config.win_score,
+            tick,
+            ack,
+            first_seq: session.remote_inputs.len() as u32,
+            events,
+            checksum: None
+        }
+    }
+
+    #[test]
+    fn acks_past_our_inputs_are_clamped() {
+        let (mut session, mut peer) = lone_session();
+        peer.send(&packet_for(&session, 0, 0, Vec::new()).encode());
+        session.poll();
+        session.add_input(Input::Up(Side::Lhs), true);
+        assert!(session.advance());
+        peer.send(&packet_for(&session, 1, 1000, Vec::new()).encode());
+        session.poll();
+        assert_eq!(session.remote_ack, 1);
+    }
+
+    #[test]
+    fn stale_and_foreign_inputs_are_dropped() {
+        let (mut session, mut peer) = lone_session();
+        peer.send(&packet_for(&session, 10, 0, Vec::new()).encode());
+        session.poll();
+        for _ in 0..20 {
+            assert!(session.advance());
+        }
+        let rejected = [
+            (2, Input::Up(Side::Rhs), true),
+            (12, Input::Up(Side::Lhs), true),
+            (12, Input::Stick(Side::Lhs, 100), true),
+            (12, Input::ToggleBallSim, true),
+            (12, Input::Restart, true)
+        ];
+        for &event in rejected.iter() {
+            peer.send(&packet_for(&session, 13, 0, vec![event]).encode());
+            session.poll();
+            assert!(session.remote_inputs.is_empty(), "{:?} was accepted", event);
+        }
+        peer.send(&packet_for(&session, 13, 0, vec![(12, Input::Down(Side::Rhs), true),
+                                                   (11, Input::Up(Side::Rhs), true)]).encode());
+        session.poll();
+        assert!(session.remote_inputs.is_empty(), "inputs out of tick order were accepted");
+        peer.send(&packet_for(&session, 13, 0, vec![(12, Input::Down(Side::Rhs), true)]).encode());
+        session.poll();
+        assert_eq!(session.remote_inputs, vec![(12, Input::Down(Side::Rhs), true)]);
+        assert_eq!(session.board().tick(), 20);
+    }
+
+    #[test]
+    fn mismatched_settings_never_connect() {
+        let (mut session, mut peer) = lone_session();
+        let mut packet = packet_for(&session, 0, 0, Vec::new());
+        packet.config_hash ^= 1;
+        peer.send(&packet.encode());
+        session.poll();
+        assert!(session.is_mismatched());
+        assert!(!session.is_connected());
+        assert!(!session.advance());
+
+        let (mut session, mut peer) = lone_session();
+        let mut packet = packet_for(&session, 0, 0, Vec::new());
+        packet.win_score += 1;
+        peer.send(&packet.encode());
+        session.poll();
+        assert!(session.is_mismatched());
+        assert!(!session.is_connected());
+    }
+}
@@ -0,0 +1,357 @@
+use ai;
+use config::GameConfig;
+use game;
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::fs::File;
+
+const MAGIC: &str = "pong-replay";
+const VERSION: u32 = 1;
+
+/// A recorded match: which side served first, the seed for the serve angles,
+/// who steered each paddle, the game settings and win score, plus every input handed to `Board::handle_input`,
+/// stamped with the board tick it was applied on.
+///
+/// On disk this is a line-based text file:
+///
+/// ```text
+/// pong-replay 1
+/// serve lhs
+/// seed 1234
+/// lhs human
+/// rhs ai:medium
+/// config 5a1b03f2c94e7d60
+/// win_score 10
+/// 240 lhs_up press
+/// 263 lhs_up release
+/// 300 rhs_stick -16384
+/// 310 lhs_aim 212
+/// end 9001
+/// ```
+///
+/// Stick and aim inputs record their value in place of press or release. The
+/// settings are only stored as `GameConfig::simulation_hash`, so playback
+/// needs the same config file.
+pub struct Replay {
+    pub lhs_start: bool,
+    pub serve_seed: Option<u64>,
+    pub lhs_controller: ai::ControllerKind,
+    pub rhs_controller: ai::ControllerKind,
+    pub config_hash: u64,
+    pub win_score: u32,
+    pub inputs: Vec<(u64, game::Input, bool)>,
+    pub end_tick: u64
+}
+
+/// An input with a value, and whether it counts as pressed.
+fn parse_valued(name: &str, value: &str) -> Option<(game::Input, bool)> {
+    let input = match name {
+        "lhs_stick" => game::Input::Stick(game::Side::Lhs, 0),
+        "rhs_stick" => game::Input::Stick(game::Side::Rhs, 0),
+        "lhs_aim" => game::Input::Aim(game::Side::Lhs, 0),
+        "rhs_aim" => game::Input::Aim(game::Side::Rhs, 0),
+        _ => return None
+    };
+    value.parse().ok().map(|value| input.with_value(value))
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+impl Replay {
+    pub fn new(lhs_start: bool, serve_seed: Option<u64>, lhs_controller: ai::ControllerKind,
+               rhs_controller: ai::ControllerKind, config: &GameConfig) -> Self {
+        Replay {
+            lhs_start,
+            serve_seed,
+            lhs_controller,
+            rhs_controller,
+            config_hash: config.simulation_hash(),
+            win_score: config.win_score,
+            inputs: Vec::new(),
+            end_tick: 0
+        }
+    }
+
+    /// Checks that `config` matches the recording's settings, and takes its
+    /// win score.
+    pub fn apply_settings(&self, config: &mut GameConfig) -> Result<(), String> {
+        if self.config_hash != config.simulation_hash() {
+            return Err("it was recorded with different game settings; pass the --config file it \
+                        was recorded with".to_string());
+        }
+        config.win_score = self.win_score;
+        Ok(())
+    }
+
+    pub fn record(&mut self, tick: u64, input: game::Input, is_pressed: bool) {
+        self.inputs.push((tick, input, is_pressed));
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{} {}", MAGIC, VERSION)?;
+        writeln!(file, "serve {}", if self.lhs_start { "lhs" } else { "rhs" })?;
+        match self.serve_seed {
+            Some(seed) => writeln!(file, "seed {}", seed)?,
+            None => writeln!(file, "seed none")?
+        }
+        writeln!(file, "lhs {}", self.lhs_controller.name())?;
+        writeln!(file, "rhs {}", self.rhs_controller.name())?;
+        writeln!(file, "config {:016x}", self.config_hash)?;
+        writeln!(file, "win_score {}", self.win_score)?;
+        for &(tick, input, is_pressed) in self.inputs.iter() {
+            match input.value() {
+                Some(value) => writeln!(file, "{} {} {}", tick, input.name(), value)?,
+                None => writeln!(file, "{} {} {}", tick, input.name(), if is_pressed { "press" } else { "release" })?
+            }
+        }
+        writeln!(file, "end {}", self.end_tick)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> io::Result<Replay> {
+        let file = BufReader::new(File::open(path)?);
+        let mut lines = file.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let mut next_line = || -> io::Result<(usize, String)> {
+            match lines.next() {
+                Some((n, line)) => Ok((n, line?)),
+                None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "replay is truncated"))
+            }
+        };
+
+        let (n, header) = next_line()?;
+        let version = match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [magic, version] if *magic == MAGIC => version.parse::<u32>()
+                .map_err(|_| invalid_data(n, "bad version number"))?,
+            _ => return Err(invalid_data(n, "not a pong replay"))
+        };
+        if version != VERSION {
+            return Err(invalid_data(n, &format!("unsupported replay version {}", version)));
+        }
+
+        let (n, serve) = next_line()?;
+        let lhs_start = match serve.trim() {
+            "serve lhs" => true,
+            "serve rhs" => false,
+            _ => return Err(invalid_data(n, "expected `serve lhs` or `serve rhs`"))
+        };
+
+        let (n, line) = next_line()?;
+        let serve_seed = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["seed", "none"] => None,
+            ["seed", seed] => Some(seed.parse().map_err(|_| invalid_data(n, "bad seed"))?),
+            _ => return Err(invalid_data(n, "expected `seed <number>` or `seed none`"))
+        };
+
+        let mut read_controller = |side: &str| -> io::Result<ai::ControllerKind> {
+            let (n, line) = next_line()?;
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [line_side, name] if *line_side == side => ai::ControllerKind::from_name(name)
+                    .ok_or_else(|| invalid_data(n, &format!("unknown controller `{}`", name))),
+                _ => Err(invalid_data(n, &format!("expected `{} <controller>`", side)))
+            }
+        };
+        let lhs_controller = read_controller("lhs")?;
+        let rhs_controller = read_controller("rhs")?;
+
+        let (n, line) = next_line()?;
+        let config_hash = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["config", hash] => u64::from_str_radix(hash, 16).map_err(|_| invalid_data(n, "bad config hash"))?,
+            _ => return Err(invalid_data(n, "expected `config <hash>`"))
+        };
+        let (n, line) = next_line()?;
+        let win_score = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["win_score", score] => score.parse().map_err(|_| invalid_data(n, "bad win score"))?,
+            _ => return Err(invalid_data(n, "expected `win_score <number>`"))
+        };
+
+        let mut replay = Replay {
+            lhs_start,
+            serve_seed,
+            lhs_controller,
+            rhs_controller,
+            config_hash,
+            win_score,
+            inputs: Vec::new(),
+            end_tick: 0
+        };
+        loop {
+            let (n, line) = next_line()?;
+            let parts: Vec<_> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["end", tick] => {
+                    replay.end_tick = tick.parse().map_err(|_| invalid_data(n, "bad tick"))?;
+                    return Ok(replay);
+                },
+                [tick, input, value] if !matches!(*value, "press" | "release") => {
+                    let tick = tick.parse().map_err(|_| invalid_data(n, "bad tick"))?;
+                    let (input, is_pressed) = parse_valued(input, value)
+                        .ok_or_else(|| invalid_data(n, &format!("bad input `{} {}`", input, value)))?;
+                    replay.inputs.push((tick, input, is_pressed));
+                },
+                [tick, input, state] => {
+                    let tick = tick.parse().map_err(|_| invalid_data(n, "bad tick"))?;
+                    let input = game::Input::from_name(input)
+                        .ok_or_else(|| invalid_data(n, &format!("unknown input `{}`", input)))?;
+                    let is_pressed = match *state {
+                        "press" => true,
+                        "release" => false,
+                        _ => return Err(invalid_data(n, "expected `press` or `release`"))
+                    };
+                    replay.inputs.push((tick, input, is_pressed));
+                },
+                _ => return Err(invalid_data(n, "expected `<tick> <input> <press|release>`"))
+            }
+        }
+    }
+}
+
+/// Saves an in-progress recording, if any, up to the board's current tick and
+/// stops recording.
+pub fn save_recording(recording: &mut Option<(String, Replay)>, board: &game::Board) {
+    if let Some((path, mut replay)) = recording.take() {
+        replay.end_tick = board.tick();
+        if let Err(e) = replay.save(&path) {
+            println!("Failed to save replay to {}: {}", path, e);
+        }
+    }
+}
+
+/// Feeds a `Replay` back into a `Board` as the board's tick advances.
+pub struct ReplayPlayer {
+    replay: Replay,
+    cursor: usize
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            cursor: 0
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Applies every recorded input that is due on the board's current tick.
+    pub fn apply(&mut self, board: &mut game::Board) {
+        while let Some(&(tick, input, is_pressed)) = self.replay.inputs.get(self.cursor) {
+            if tick > board.tick() {
+                break;
+            }
+            board.handle_input(input, is_pressed);
+            self.cursor += 1;
+        }
+    }
+
+    pub fn is_finished(&self, board: &game::Board) -> bool {
+        self.cursor == self.replay.inputs.len() && board.tick() >= self.replay.end_tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn temp_path(name: &str) -> String {
+        env::temp_dir().join(format!("pong-test-{}-{}", process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    fn load_text(name: &str, text: &str) -> io::Result<Replay> {
+        let path = temp_path(name);
+        fs::write(&path, text).unwrap();
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        replay
+    }
+
+    fn sample() -> Replay {
+        let config = GameConfig { win_score: 3, ..GameConfig::default() };
+        let mut replay = Replay::new(false, Some(1234), ai::ControllerKind::Human,
+                                     ai::ControllerKind::Ai(ai::Difficulty::Hard), &config);
+        replay.record(240, game::Input::Up(game::Side::Lhs), true);
+        replay.record(263, game::Input::Up(game::Side::Lhs), false);
+        replay.record(300, game::Input::Stick(game::Side::Lhs, -16384), true);
+        replay.record(310, game::Input::Aim(game::Side::Lhs, 212), true);
+        replay.end_tick = 9001;
+        replay
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let path = temp_path("round-trip");
+        let replay = sample();
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!loaded.lhs_start);
+        assert_eq!(loaded.serve_seed, Some(1234));
+        assert_eq!(loaded.lhs_controller, ai::ControllerKind::Human);
+        assert_eq!(loaded.rhs_controller, ai::ControllerKind::Ai(ai::Difficulty::Hard));
+        assert_eq!(loaded.config_hash, GameConfig::default().simulation_hash());
+        assert_eq!(loaded.win_score, 3);
+        assert_eq!(loaded.inputs, replay.inputs);
+        assert_eq!(loaded.end_tick, 9001);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(load_text("magic", "not-a-replay 1\n").is_err());
+        assert!(load_text("future", &format!("pong-replay {}\n", VERSION + 1)).is_err());
+        assert!(load_text("truncated", "pong-replay 1\nserve lhs\nseed none\n").is_err());
+        assert!(load_text("no-config", "pong-replay 1\nserve lhs\nseed none\nlhs human\nrhs human\n\
+                                        10 lhs_up press\nend 20\n").is_err());
+        assert!(load_text("input", "pong-replay 1\nserve lhs\nseed none\nlhs human\nrhs human\n\
+                                    config 0\nwin_score 10\n10 up press\nend 20\n").is_err());
+    }
+
+    #[test]
+    fn settings_must_match() {
+        let replay = sample();
+        let mut config = GameConfig::default();
+        replay.apply_settings(&mut config).unwrap();
+        assert_eq!(config.win_score, 3);
+        config.ball_speedup = 1.1;
+        assert!(replay.apply_settings(&mut config).is_err());
+    }
+
+    #[test]
+    fn playback_reproduces_the_match() {
+        let config = GameConfig::default();
+        let new_board = || {
+            let mut board = game::Board::new(config.clone());
+            board.set_controller(game::Side::Lhs, game::Controller::Human);
+            board.set_controller(game::Side::Rhs, ai::ControllerKind::Ai(ai::Difficulty::Easy).controller());
+            board.set_serve_seed(99);
+            board.start_game(true);
+            board
+        };
+        let mut board = new_board();
+        let mut replay = Replay::new(true, Some(99), ai::ControllerKind::Human,
+                                     ai::ControllerKind::Ai(ai::Difficulty::Easy), &config);
+        for tick in 0..20 * u64::from(game::TICK_RATE) {
+            if tick % 50 == 0 {
+                let input = if tick % 100 == 0 { game::Input::Up(game::Side::Lhs) } else { game::Input::Down(game::Side::Lhs) };
+                board.handle_input(input, true);
+                replay.record(board.tick(), input, true);
+            }
+            board.update(game::TICK_DT);
+        }
+        replay.end_tick = board.tick();
+
+        let mut played = new_board();
+        let mut player = ReplayPlayer::new(replay);
+        while !player.is_finished(&played) {
+            player.apply(&mut played);
+            played.update(game::TICK_DT);
+        }
+        assert_eq!(played.checksum(), board.checksum());
+    }
+}
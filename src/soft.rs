@@ -0,0 +1,256 @@
+use effects;
+use game;
+use graphics::{Canvas, Renderer, Shape};
+use state;
+use theme;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+// Largest payload of a stored (uncompressed) deflate block.
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// Rasterizes the same rects as `BoardRenderer` into an RGBA buffer on the
+/// CPU, so frames can be captured on machines without a GPU.
+pub struct SoftRenderer {
+    width: u32,
+    height: u32,
+    scale: f32,
+    pixels: Vec<u8>
+}
+
+impl SoftRenderer {
+    /// `scale` is the number of image pixels per board unit.
+    pub fn new(width: u32, height: u32, scale: f32) -> Self {
+        SoftRenderer {
+            width,
+            height,
+            scale,
+            pixels: vec![0; (width * height * 4) as usize]
+        }
+    }
+
+    pub fn save_png(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_png(&mut file, self.width, self.height, &self.pixels)?;
+        file.flush()
+    }
+
+    fn clear(&mut self, color: theme::Color) {
+        let color = theme::to_bytes(color);
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+}
+
+impl Canvas for SoftRenderer {
+    fn draw_shape(&mut self, board: &game::Board, rect: game::Rect, shape: Shape, color: theme::Color) {
+        // Same centring as the vertex shader. Each pixel is blended by how far
+        // its centre is from the edge, as the fragment shader does.
+        let offset_x = (self.width as f32 / self.scale - board.width) / 2.;
+        let offset_y = (self.height as f32 / self.scale - board.height) / 2.;
+        let to_pixel = |value: f32, offset: f32, limit: u32| {
+            (((value + offset) * self.scale).max(0.) as u32).min(limit)
+        };
+        let (x0, x1) = (to_pixel(rect.x, offset_x, self.width), to_pixel(rect.x + rect.width + 1. / self.scale, offset_x, self.width));
+        let (y0, y1) = (to_pixel(rect.y, offset_y, self.height), to_pixel(rect.y + rect.height + 1. / self.scale, offset_y, self.height));
+        for y in y0..y1 {
+            // Board y points up, image rows go down.
+            let row = (self.height - 1 - y) * self.width;
+            let board_y = (y as f32 + 0.5) / self.scale - offset_y;
+            for x in x0..x1 {
+                let board_x = (x as f32 + 0.5) / self.scale - offset_x;
+                let distance = shape.distance(&rect, board_x, board_y) * self.scale;
+                let alpha = color[3] * (0.5 - distance).clamp(0., 1.);
+                if alpha <= 0. {
+                    continue;
+                }
+                let i = ((row + x) * 4) as usize;
+                for (pixel, &channel) in self.pixels[i..i + 3].iter_mut().zip(color.iter()) {
+                    *pixel = (f32::from(*pixel) * (1. - alpha) + channel * 255. * alpha).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for SoftRenderer {
+    fn handle_frame_resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0; (width * height * 4) as usize];
+    }
+
+    fn draw(&mut self, board: &game::Board, flow: &state::Match, effects: &effects::Effects) {
+        self.clear(board.config.graphics.theme().background);
+        self.draw_scene(board, flow, effects, None);
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc_input = kind.to_vec();
+    crc_input.extend_from_slice(data);
+    out.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+/// Encodes 8-bit RGBA rows as a PNG. The image data is stored without
+/// compression, which keeps the encoder tiny at the cost of file size.
+pub fn write_png<W: Write>(out: &mut W, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    out.write_all(PNG_SIGNATURE)?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type RGBA, default compression, filter and no
+    // interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(out, b"IHDR", &header)?;
+
+    // Every scanline starts with its filter type, which is always none.
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks((width * 4) as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let block_count = raw.len().div_ceil(MAX_STORED_BLOCK);
+    for (i, block) in raw.chunks(MAX_STORED_BLOCK).enumerate() {
+        zlib.push(if i + 1 == block_count { 1 } else { 0 });
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    write_chunk(out, b"IDAT", &zlib)?;
+
+    write_chunk(out, b"IEND", &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use hash::Fnv;
+    use std::env;
+
+    // One image pixel per board unit.
+    const SCALE: f32 = 1.;
+    // FNV-1a hashes of the pixels of the scenes below. When a layout change
+    // is intended, check the PNG the failing test saves and update the hash.
+    const TITLE_HASH: u64 = 0x46cf6ffe3d7ac629;
+    const PLAYING_HASH: u64 = 0x2a20d27210f9a665;
+
+    fn render(board: &game::Board, flow: &state::Match) -> SoftRenderer {
+        let padding = board.config.graphics.board_padding;
+        let width = ((board.width + 2. * padding) * SCALE) as u32;
+        let height = ((board.height + 2. * padding) * SCALE) as u32;
+        let mut renderer = SoftRenderer::new(width, height, SCALE);
+        renderer.draw(board, flow, &effects::Effects::new());
+        renderer
+    }
+
+    fn pixel_hash(renderer: &SoftRenderer) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write_bytes(&renderer.pixels);
+        hash.finish()
+    }
+
+    fn assert_golden(renderer: &SoftRenderer, name: &str, expected: u64) {
+        let hash = pixel_hash(renderer);
+        if hash != expected {
+            let path = env::temp_dir().join(format!("pong-golden-{}.png", name));
+            renderer.save_png(&path.to_string_lossy()).unwrap();
+            panic!("{} renders with hash {:#018x} instead of {:#018x}; see {}", name, hash, expected, path.display());
+        }
+    }
+
+    /// The RGB of the image pixel over board point `(x, y)`.
+    fn pixel_at(renderer: &SoftRenderer, board: &game::Board, x: f32, y: f32) -> [u8; 3] {
+        let padding = board.config.graphics.board_padding;
+        let column = ((x + padding) * SCALE) as u32;
+        let row = renderer.height - 1 - ((y + padding) * SCALE) as u32;
+        let i = ((row * renderer.width + column) * 4) as usize;
+        [renderer.pixels[i], renderer.pixels[i + 1], renderer.pixels[i + 2]]
+    }
+
+    fn rgb(color: theme::Color) -> [u8; 3] {
+        let bytes = theme::to_bytes(color);
+        [bytes[0], bytes[1], bytes[2]]
+    }
+
+    fn served_board() -> game::Board {
+        let mut board = game::Board::new(GameConfig::default());
+        board.start_game(true);
+        board
+    }
+
+    #[test]
+    fn title_screen_matches_golden() {
+        let board = served_board();
+        let renderer = render(&board, &state::Match::new(true));
+        assert_golden(&renderer, "title", TITLE_HASH);
+    }
+
+    #[test]
+    fn playing_layout_matches_golden() {
+        let mut board = served_board();
+        board.lhs_score = 3;
+        board.rhs_score = 12;
+        for _ in 0..30 {
+            board.update(game::TICK_DT);
+        }
+        let renderer = render(&board, &state::Match::playing());
+        assert_golden(&renderer, "playing", PLAYING_HASH);
+
+        let theme = board.config.graphics.theme();
+        let paddle = board.lhs_paddle.bound;
+        let ball = board.ball.bound;
+        assert_eq!(pixel_at(&renderer, &board, paddle.x + paddle.width / 2., paddle.y + paddle.height / 2.),
+                   rgb(theme.lhs_paddle));
+        assert_eq!(pixel_at(&renderer, &board, ball.x + ball.width / 2., ball.y + ball.height / 2.), rgb(theme.ball));
+        // Just inside the bottom left corner, away from everything.
+        assert_eq!(pixel_at(&renderer, &board, 5., board.height / 4.), rgb(theme.background));
+        // The top border runs the whole width of the board.
+        let border = board.config.graphics.border_width;
+        assert_eq!(pixel_at(&renderer, &board, board.width / 3., board.height + border / 2.), rgb(theme.border));
+    }
+
+    #[test]
+    fn png_has_valid_chunks() {
+        let mut png = Vec::new();
+        write_png(&mut png, 2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+        assert_eq!(&png[..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        // The well known CRC of an empty IEND chunk.
+        assert_eq!(&png[png.len() - 8..], &[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+}
@@ -0,0 +1,420 @@
+use game;
+use game::{Board, Event, Input, Side};
+use input::Action;
+
+/// How long the serve countdown lasts.
+pub const COUNTDOWN_TICKS: u32 = 3 * game::TICK_RATE;
+/// How long the board holds still after a goal.
+pub const POINT_SCORED_TICKS: u32 = game::TICK_RATE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Play,
+    Resume,
+    Rematch,
+    Quit
+}
+
+impl MenuItem {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            MenuItem::Play => "Play",
+            MenuItem::Resume => "Resume",
+            MenuItem::Rematch => "Rematch",
+            MenuItem::Quit => "Quit"
+        }
+    }
+}
+
+const TITLE_MENU: &[MenuItem] = &[MenuItem::Play, MenuItem::Quit];
+const PAUSE_MENU: &[MenuItem] = &[MenuItem::Resume, MenuItem::Quit];
+const GAME_OVER_MENU: &[MenuItem] = &[MenuItem::Rematch, MenuItem::Quit];
+const REPLAY_OVER_MENU: &[MenuItem] = &[MenuItem::Quit];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum State {
+    Title {
+        selected: usize
+    },
+    /// Ticks left until the ball is served.
+    Countdown {
+        remaining: u32
+    },
+    Playing,
+    /// Holds the state to go back to on resume.
+    Paused {
+        selected: usize,
+        resume: Box<State>
+    },
+    PointScored {
+        scorer: Side,
+        remaining: u32
+    },
+    GameOver {
+        lhs_won: bool,
+        selected: usize
+    }
+}
+
+fn default_names() -> [String; 2] {
+    ["Left".to_string(), "Right".to_string()]
+}
+
+/// The flow of a local match around the `Board`: menus, countdowns and
+/// pausing. The board is only simulated while `Playing`, so everything else
+/// leaves its tick count, and thus replays, untouched.
+pub struct Match {
+    state: State,
+    can_rematch: bool,
+    /// Watching a replay, which plays its own recorded paddle inputs.
+    watching: bool,
+    lhs_start: bool,
+    names: [String; 2],
+    quit: bool,
+    countdown_beep: Option<u32>
+}
+
+impl Match {
+    /// Starts on the title screen. The board should already be served.
+    pub fn new(lhs_start: bool) -> Self {
+        Match {
+            state: State::Title { selected: 0 },
+            can_rematch: true,
+            watching: false,
+            lhs_start,
+            names: default_names(),
+            quit: false,
+            countdown_beep: None
+        }
+    }
+
+    /// Skips the title screen and offers no rematch, since a replay can only
+    /// be watched as recorded. Paddle keys only move through the menus.
+    pub fn for_replay() -> Self {
+        Match {
+            state: State::Countdown { remaining: COUNTDOWN_TICKS },
+            can_rematch: false,
+            watching: true,
+            lhs_start: true,
+            names: default_names(),
+            quit: false,
+            countdown_beep: None
+        }
+    }
+
+    /// Stays in `Playing`, for netplay where both peers simulate in lockstep
+    /// and neither can pause on its own.
+    pub fn playing() -> Self {
+        Match {
+            state: State::Playing,
+            can_rematch: false,
+            watching: false,
+            lhs_start: true,
+            names: default_names(),
+            quit: false,
+            countdown_beep: None
+        }
+    }
+
+    /// Jumps to the state the board's score calls for, after a match was
+    /// simulated without going through the menus.
+    pub fn skip_to_board(&mut self, board: &Board) {
+        self.state = match board.winner() {
+            Some(lhs_won) => State::GameOver { lhs_won, selected: 0 },
+            None => State::Playing
+        };
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Names shown by each side's score and in the winner's message.
+    pub fn set_names(&mut self, lhs: &str, rhs: &str) {
+        self.names = [lhs.to_string(), rhs.to_string()];
+    }
+
+    pub fn name(&self, side: Side) -> &str {
+        match side {
+            Side::Lhs => &self.names[0],
+            Side::Rhs => &self.names[1]
+        }
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// The menu shown in the current state, if any, with the selected entry.
+    pub fn menu(&self) -> Option<(&'static [MenuItem], usize)> {
+        match self.state {
+            State::Title { selected } => Some((TITLE_MENU, selected)),
+            State::Paused { selected, .. } => Some((PAUSE_MENU, selected)),
+            State::GameOver { selected, .. } if self.can_rematch => Some((GAME_OVER_MENU, selected)),
+            State::GameOver { selected, .. } => Some((REPLAY_OVER_MENU, selected)),
+            _ => None
+        }
+    }
+
+    fn select(&mut self, step: isize) {
+        let len = match self.menu() {
+            Some((items, _)) => items.len() as isize,
+            None => return
+        };
+        match self.state {
+            State::Title { ref mut selected } |
+            State::Paused { ref mut selected, .. } |
+            State::GameOver { ref mut selected, .. } => {
+                *selected = (*selected as isize + step).rem_euclid(len) as usize;
+            },
+            _ => ()
+        }
+    }
+
+    fn activate(&mut self, board: &mut Board, item: MenuItem) {
+        match item {
+            MenuItem::Play => self.state = State::Countdown { remaining: COUNTDOWN_TICKS },
+            MenuItem::Resume => {
+                if let State::Paused { ref resume, .. } = self.state {
+                    self.state = (**resume).clone();
+                }
+            },
+            MenuItem::Rematch => {
+                board.new_match(self.lhs_start);
+                self.state = State::Countdown { remaining: COUNTDOWN_TICKS };
+            },
+            MenuItem::Quit => self.quit = true
+        }
+    }
+
+    /// Reacts to a key. Returns the input if it was handed on to the board,
+    /// so the caller can record it.
+    pub fn handle_action(&mut self, board: &mut Board, action: Action, is_pressed: bool) -> Option<Input> {
+        match action {
+            Action::Board(input) => {
+                if is_pressed && self.menu().is_some() {
+                    match input {
+                        Input::Up(_) => self.select(-1),
+                        Input::Down(_) => self.select(1),
+                        _ => ()
+                    }
+                    return None;
+                }
+                if self.watching {
+                    return None;
+                }
+                // Releases always get through, even in a menu, or a paddle
+                // held across a pause would keep accelerating.
+                board.handle_input(input, is_pressed);
+                Some(input)
+            },
+            Action::Pause if is_pressed => {
+                self.state = match self.state {
+                    State::Countdown { .. } | State::Playing | State::PointScored { .. } => State::Paused {
+                        selected: 0,
+                        resume: Box::new(self.state.clone())
+                    },
+                    State::Paused { ref resume, .. } => (**resume).clone(),
+                    ref state => state.clone()
+                };
+                None
+            },
+            Action::Confirm if is_pressed => {
+                if let Some((items, selected)) = self.menu() {
+                    self.activate(board, items[selected]);
+                }
+                None
+            },
+            _ => None
+        }
+    }
+
+    /// Advances the timers by one tick. Returns whether the board should be
+    /// simulated on this tick.
+    pub fn tick(&mut self, board: &Board) -> bool {
+        self.state = match self.state {
+            State::Playing => return true,
+            State::Countdown { remaining } if remaining > 1 => {
+                if remaining % game::TICK_RATE == 0 {
+                    self.countdown_beep = Some(remaining / game::TICK_RATE);
+                }
+                State::Countdown { remaining: remaining - 1 }
+            },
+            State::Countdown { .. } => {
+                self.countdown_beep = Some(0);
+                State::Playing
+            },
+            State::PointScored { scorer, remaining } if remaining > 1 => {
+                State::PointScored { scorer, remaining: remaining - 1 }
+            },
+            State::PointScored { .. } => match board.winner() {
+                Some(lhs_won) => State::GameOver { lhs_won, selected: 0 },
+                None => State::Countdown { remaining: COUNTDOWN_TICKS }
+            },
+            ref state => state.clone()
+        };
+        false
+    }
+
+    /// Takes the countdown beep due since the last call, if any: the whole
+    /// seconds left as each one starts, then 0 as the ball is served.
+    pub fn take_countdown_beep(&mut self) -> Option<u32> {
+        self.countdown_beep.take()
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::Goal(scorer, _) = *event {
+            self.state = State::PointScored { scorer, remaining: POINT_SCORED_TICKS };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+
+    fn served_board() -> Board {
+        let mut board = Board::new(GameConfig::default());
+        board.set_controller(Side::Lhs, game::Controller::Human);
+        board.start_game(true);
+        board
+    }
+
+    fn press(flow: &mut Match, board: &mut Board, action: Action) -> Option<Input> {
+        let input = flow.handle_action(board, action, true);
+        flow.handle_action(board, action, false);
+        input
+    }
+
+    fn ticks(flow: &mut Match, board: &mut Board, count: u32) {
+        for _ in 0..count {
+            if flow.tick(board) {
+                board.update(game::TICK_DT);
+                for event in board.drain_events() {
+                    flow.handle_event(&event);
+                }
+            }
+        }
+    }
+
+    const UP: Action = Action::Board(Input::Up(Side::Lhs));
+    const DOWN: Action = Action::Board(Input::Down(Side::Lhs));
+
+    #[test]
+    fn title_counts_down_into_play() {
+        let mut board = served_board();
+        let mut flow = Match::new(true);
+        assert_eq!(flow.menu(), Some((TITLE_MENU, 0)));
+        press(&mut flow, &mut board, Action::Confirm);
+        assert_eq!(*flow.state(), State::Countdown { remaining: COUNTDOWN_TICKS });
+        let mut beeps = Vec::new();
+        for _ in 0..COUNTDOWN_TICKS {
+            assert!(!flow.tick(&board));
+            beeps.extend(flow.take_countdown_beep());
+        }
+        assert_eq!(beeps, vec![3, 2, 1, 0]);
+        assert_eq!(*flow.state(), State::Playing);
+        assert!(flow.tick(&board));
+        assert_eq!(board.tick(), 0);
+    }
+
+    #[test]
+    fn quit_from_the_title() {
+        let mut board = served_board();
+        let mut flow = Match::new(true);
+        press(&mut flow, &mut board, DOWN);
+        assert_eq!(flow.menu(), Some((TITLE_MENU, 1)));
+        press(&mut flow, &mut board, DOWN);
+        assert_eq!(flow.menu(), Some((TITLE_MENU, 0)));
+        press(&mut flow, &mut board, UP);
+        press(&mut flow, &mut board, Action::Confirm);
+        assert!(flow.should_quit());
+    }
+
+    #[test]
+    fn pause_holds_the_board_and_resumes_where_it_was() {
+        let mut board = served_board();
+        let mut flow = Match::new(true);
+        flow.skip_to_board(&board);
+        press(&mut flow, &mut board, Action::Pause);
+        assert_eq!(*flow.state(), State::Paused { selected: 0, resume: Box::new(State::Playing) });
+        ticks(&mut flow, &mut board, 10);
+        assert_eq!(board.tick(), 0);
+        // Paddle keys pick menu entries instead of steering.
+        assert_eq!(press(&mut flow, &mut board, DOWN), None);
+        assert_eq!(flow.menu(), Some((PAUSE_MENU, 1)));
+        assert_eq!(board.lhs_paddle.ddy, 0.);
+        press(&mut flow, &mut board, UP);
+        press(&mut flow, &mut board, Action::Confirm);
+        assert_eq!(*flow.state(), State::Playing);
+        assert_eq!(press(&mut flow, &mut board, UP), Some(Input::Up(Side::Lhs)));
+    }
+
+    #[test]
+    fn releases_reach_the_board_while_paused() {
+        let mut board = served_board();
+        let mut flow = Match::new(true);
+        flow.skip_to_board(&board);
+        flow.handle_action(&mut board, UP, true);
+        assert!(board.lhs_paddle.ddy > 0.);
+        press(&mut flow, &mut board, Action::Pause);
+        assert_eq!(flow.handle_action(&mut board, UP, false), Some(Input::Up(Side::Lhs)));
+        assert_eq!(board.lhs_paddle.ddy, 0.);
+    }
+
+    #[test]
+    fn goals_hold_then_count_down_again() {
+        let mut board = served_board();
+        let mut flow = Match::new(true);
+        flow.skip_to_board(&board);
+        board.lhs_paddle.bound.y = 0.;
+        while board.rhs_score == 0 {
+            ticks(&mut flow, &mut board, 1);
+        }
+        assert_eq!(*flow.state(), State::PointScored { scorer: Side::Rhs, remaining: POINT_SCORED_TICKS });
+        let tick = board.tick();
+        ticks(&mut flow, &mut board, POINT_SCORED_TICKS);
+        assert_eq!(board.tick(), tick);
+        assert_eq!(*flow.state(), State::Countdown { remaining: COUNTDOWN_TICKS });
+    }
+
+    #[test]
+    fn winning_point_ends_the_match_and_rematch_restarts_it() {
+        let mut board = served_board();
+        board.rhs_score = board.config.win_score - 1;
+        let mut flow = Match::new(true);
+        flow.skip_to_board(&board);
+        board.lhs_paddle.bound.y = 0.;
+        ticks(&mut flow, &mut board, 5 * game::TICK_RATE);
+        assert_eq!(*flow.state(), State::GameOver { lhs_won: false, selected: 0 });
+        assert_eq!(flow.menu(), Some((GAME_OVER_MENU, 0)));
+        press(&mut flow, &mut board, Action::Confirm);
+        assert_eq!((board.lhs_score, board.rhs_score), (0, 0));
+        assert_eq!(*flow.state(), State::Countdown { remaining: COUNTDOWN_TICKS });
+    }
+
+    #[test]
+    fn replay_viewers_can_use_the_pause_menu_but_not_the_paddles() {
+        let mut board = served_board();
+        let mut flow = Match::for_replay();
+        ticks(&mut flow, &mut board, COUNTDOWN_TICKS);
+        assert_eq!(*flow.state(), State::Playing);
+        assert_eq!(press(&mut flow, &mut board, UP), None);
+        assert_eq!(board.lhs_paddle.ddy, 0.);
+        press(&mut flow, &mut board, Action::Pause);
+        press(&mut flow, &mut board, DOWN);
+        assert_eq!(flow.menu(), Some((PAUSE_MENU, 1)));
+        press(&mut flow, &mut board, Action::Confirm);
+        assert!(flow.should_quit());
+    }
+
+    #[test]
+    fn replays_offer_no_rematch() {
+        let mut board = served_board();
+        let mut flow = Match::for_replay();
+        board.rhs_score = board.config.win_score;
+        flow.skip_to_board(&board);
+        assert_eq!(flow.menu(), Some((REPLAY_OVER_MENU, 0)));
+    }
+}
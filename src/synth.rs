@@ -0,0 +1,142 @@
+use std::f32::consts::PI;
+
+#[cfg(any(feature = "audio", test))]
+pub const SAMPLE_RATE: u32 = 44100;
+// Fade in and out over this long so blips start and stop without clicks.
+const FADE_SECONDS: f32 = 0.004;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            _ => None
+        }
+    }
+}
+
+/// A single tone that slides from `frequency` to `end_frequency` and decays
+/// linearly to silence over `duration` seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Blip {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub end_frequency: f32,
+    pub duration: f32,
+    /// Peak amplitude from 0 to 1.
+    pub volume: f32
+}
+
+impl Blip {
+    /// Mono samples at `sample_rate`.
+    pub fn render(&self, sample_rate: u32) -> Vec<i16> {
+        let count = (self.duration.max(0.) * sample_rate as f32).round() as usize;
+        let fade = (FADE_SECONDS * sample_rate as f32).max(1.);
+        // Phase is accumulated rather than computed from time, so the pitch
+        // slide stays smooth.
+        let mut phase = 0.;
+        (0..count).map(|i| {
+            let t = i as f32 / count as f32;
+            let frequency = self.frequency + (self.end_frequency - self.frequency) * t;
+            phase = (phase + frequency / sample_rate as f32) % 1.;
+            let wave = match self.waveform {
+                Waveform::Square => if phase < 0.5 { 1. } else { -1. },
+                Waveform::Sine => (2. * PI * phase).sin()
+            };
+            let envelope = (1. - t) * (i as f32 / fade).min(1.) * ((count - i) as f32 / fade).min(1.);
+            (wave * envelope * self.volume.clamp(0., 1.) * i16::MAX as f32) as i16
+        }).collect()
+    }
+}
+
+/// Encodes 16-bit mono samples as a PCM WAV file.
+#[cfg(any(feature = "audio", test))]
+pub fn write_wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel, the sample rate, bytes per second, bytes per frame and
+    // bits per sample.
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blip(waveform: Waveform) -> Blip {
+        Blip { waveform, frequency: 440., end_frequency: 220., duration: 0.1, volume: 0.5 }
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    #[test]
+    fn wav_has_a_pcm_header_and_every_sample() {
+        let samples = blip(Waveform::Square).render(SAMPLE_RATE);
+        let wav = write_wav(&samples, SAMPLE_RATE);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&wav, 16), 16);
+        assert_eq!(u16_at(&wav, 20), 1);
+        assert_eq!(u16_at(&wav, 22), 1);
+        assert_eq!(u32_at(&wav, 24), SAMPLE_RATE);
+        assert_eq!(u32_at(&wav, 28), SAMPLE_RATE * 2);
+        assert_eq!(u16_at(&wav, 32), 2);
+        assert_eq!(u16_at(&wav, 34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40) as usize, samples.len() * 2);
+        assert_eq!(wav.len(), 44 + samples.len() * 2);
+        let decoded: Vec<i16> = wav[44..].chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn blips_last_their_duration_and_stay_under_their_volume() {
+        for &waveform in &[Waveform::Square, Waveform::Sine] {
+            let blip = blip(waveform);
+            let samples = blip.render(SAMPLE_RATE);
+            assert_eq!(samples.len(), (blip.duration * SAMPLE_RATE as f32).round() as usize);
+            let peak = samples.iter().map(|&sample| i32::from(sample).abs()).max().unwrap();
+            assert!(peak > 0);
+            assert!(peak as f32 <= blip.volume * i16::MAX as f32);
+            assert_eq!(samples[0], 0);
+        }
+    }
+
+    #[test]
+    fn empty_blip_is_a_valid_wav() {
+        let samples = Blip { duration: 0., ..blip(Waveform::Sine) }.render(SAMPLE_RATE);
+        assert!(samples.is_empty());
+        let wav = write_wav(&samples, SAMPLE_RATE);
+        assert_eq!(wav.len(), 44);
+        assert_eq!(u32_at(&wav, 40), 0);
+    }
+}
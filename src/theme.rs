@@ -0,0 +1,120 @@
+/// RGBA, each channel in `[0, 1]`.
+pub type Color = [f32; 4];
+
+const fn rgb(r: f32, g: f32, b: f32) -> Color {
+    [r, g, b, 1.]
+}
+
+const BLACK: Color = rgb(0., 0., 0.);
+const WHITE: Color = rgb(1., 1., 1.);
+const BLUE: Color = rgb(0., 0., 1.);
+const YELLOW: Color = rgb(1., 1., 0.);
+const AMBER: Color = rgb(1., 0.5, 0.);
+
+/// Colors for every kind of primitive on screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub lhs_paddle: Color,
+    pub rhs_paddle: Color,
+    pub ball: Color,
+    pub net: Color,
+    pub border: Color,
+    pub lhs_score: Color,
+    pub rhs_score: Color,
+    pub text: Color
+}
+
+pub const DEFAULT_THEME: &str = "blue";
+
+const fn plain(background: Color, foreground: Color) -> Theme {
+    Theme {
+        background,
+        lhs_paddle: foreground,
+        rhs_paddle: foreground,
+        ball: foreground,
+        net: foreground,
+        border: foreground,
+        lhs_score: foreground,
+        rhs_score: foreground,
+        text: foreground
+    }
+}
+
+const THEMES: &[(&str, Theme)] = &[
+    ("classic", plain(BLACK, WHITE)),
+    ("blue", plain(BLUE, WHITE)),
+    ("high-contrast", Theme {
+        background: BLACK,
+        lhs_paddle: YELLOW,
+        rhs_paddle: YELLOW,
+        ball: YELLOW,
+        net: WHITE,
+        border: WHITE,
+        lhs_score: WHITE,
+        rhs_score: WHITE,
+        text: YELLOW
+    }),
+    ("per-player", Theme {
+        background: rgb(0.08, 0.08, 0.12),
+        lhs_paddle: rgb(1., 0.35, 0.3),
+        rhs_paddle: rgb(0.3, 0.75, 1.),
+        ball: WHITE,
+        net: rgb(0.4, 0.4, 0.45),
+        border: rgb(0.6, 0.6, 0.65),
+        lhs_score: rgb(1., 0.35, 0.3),
+        rhs_score: rgb(0.3, 0.75, 1.),
+        text: WHITE
+    })
+];
+
+impl Theme {
+    pub fn from_name(name: &str) -> Option<Theme> {
+        THEMES.iter().find(|&&(theme_name, _)| theme_name == name).map(|&(_, theme)| theme)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        THEMES.iter().map(|&(name, _)| name).collect()
+    }
+}
+
+/// Blends from `from` at 0 to `to` at 1.
+pub fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let mut color = from;
+    for (channel, &target) in color.iter_mut().zip(to.iter()) {
+        *channel += (target - *channel) * amount;
+    }
+    color
+}
+
+/// What a hit flash blends `color` towards: white, unless `color` is already
+/// bright enough that white wouldn't show, in which case amber.
+pub fn flash_target(color: Color) -> Color {
+    let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+    if luminance > 0.8 { AMBER } else { WHITE }
+}
+
+/// Converts to 8-bit channels, for backends that draw into bytes.
+pub fn to_bytes(color: Color) -> [u8; 4] {
+    let byte = |channel: f32| (channel.clamp(0., 1.) * 255.).round() as u8;
+    [byte(color[0]), byte(color[1]), byte(color[2]), byte(color[3])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: Color, b: Color) -> f32 {
+        a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum()
+    }
+
+    #[test]
+    fn every_paddle_visibly_flashes() {
+        for &(name, theme) in THEMES {
+            for &paddle in &[theme.lhs_paddle, theme.rhs_paddle] {
+                assert!(distance(paddle, flash_target(paddle)) >= 0.4,
+                        "{} paddles barely change when they flash", name);
+            }
+        }
+    }
+}
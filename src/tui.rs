@@ -0,0 +1,446 @@
+use font;
+use effects;
+use game;
+use graphics::{Canvas, Renderer, Shape};
+use input::Action;
+use replay;
+use state;
+use theme;
+use std::io;
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Terminals only report key presses, so a press counts as held for this long,
+// which is enough to bridge the delay before auto-repeat kicks in...
+const KEY_HOLD_TICKS: u32 = game::TICK_RATE / 2;
+// ...and each repeat extends the hold by this much.
+const KEY_REPEAT_TICKS: u32 = game::TICK_RATE / 10;
+// Redraw at most this often; terminals can't keep up with every tick.
+const FRAME_TIME: Duration = Duration::from_millis(1000 / 30);
+// How often to check whether the terminal was resized.
+const RESIZE_CHECK_FRAMES: u32 = 30;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Char(char)
+}
+
+/// Splits raw stdin bytes into keys. A lone escape is the Escape key; one
+/// followed by `[` starts an arrow key sequence. A sequence can be split
+/// across reads, so an unfinished one is held back until the next `feed`.
+#[derive(Default)]
+struct KeyParser {
+    pending: Vec<u8>
+}
+
+impl KeyParser {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Key> {
+        self.pending.extend_from_slice(bytes);
+        let mut keys = Vec::new();
+        let mut i = 0;
+        while i < self.pending.len() {
+            match &self.pending[i..] {
+                [0x1b] | [0x1b, b'['] => break,
+                [0x1b, b'[', code, ..] => {
+                    match *code {
+                        b'A' => keys.push(Key::Up),
+                        b'B' => keys.push(Key::Down),
+                        _ => ()
+                    }
+                    i += 2;
+                },
+                [0x1b, ..] => keys.push(Key::Escape),
+                [b'\r', ..] | [b'\n', ..] => keys.push(Key::Enter),
+                [byte, ..] => keys.push(Key::Char(*byte as char)),
+                [] => unreachable!()
+            }
+            i += 1;
+        }
+        self.pending.drain(..i);
+        keys
+    }
+
+    /// Called once no more input has come in: an escape on its own was the
+    /// Escape key. A started `ESC [` can only be a sequence, so it waits.
+    fn flush(&mut self) -> Vec<Key> {
+        if self.pending == [0x1b] {
+            self.pending.clear();
+            return vec![Key::Escape];
+        }
+        Vec::new()
+    }
+}
+
+fn key_action(key: Key, two_player: bool) -> Option<Action> {
+    let (arrow_side, letters) = if two_player { (game::Side::Rhs, true) } else { (game::Side::Lhs, false) };
+    match key {
+        Key::Up => Some(Action::Board(game::Input::Up(arrow_side))),
+        Key::Down => Some(Action::Board(game::Input::Down(arrow_side))),
+        Key::Char('w') if letters => Some(Action::Board(game::Input::Up(game::Side::Lhs))),
+        Key::Char('s') if letters => Some(Action::Board(game::Input::Down(game::Side::Lhs))),
+        Key::Enter | Key::Char(' ') => Some(Action::Confirm),
+        Key::Escape | Key::Char('p') => Some(Action::Pause),
+        _ => None
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Columns and rows of the terminal.
+fn terminal_size() -> io::Result<(u32, u32)> {
+    let size = stty(&["size"])?;
+    match size.split_whitespace().map(|n| n.parse::<u32>()).collect::<Vec<_>>().as_slice() {
+        [Ok(rows), Ok(cols)] => Ok((*cols, *rows)),
+        _ => Err(io::Error::other(format!("unexpected terminal size `{}`", size)))
+    }
+}
+
+/// Puts the terminal in raw mode and restores it when dropped.
+struct RawMode {
+    saved: String
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        // Hide the cursor and clear the screen.
+        print!("\x1b[?25l\x1b[2J");
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[2J\x1b[H\x1b[?25h");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+type Rgb = [u8; 3];
+
+fn to_rgb(color: theme::Color) -> Rgb {
+    let bytes = theme::to_bytes(color);
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+/// Draws into a grid of half-block characters, two square-ish pixels per
+/// terminal cell, with text printed as plain characters. Colors use 24-bit
+/// escape codes.
+pub struct TuiRenderer {
+    columns: u32,
+    rows: u32,
+    scale: f32,
+    width: f32,
+    height: f32,
+    background: Rgb,
+    pixels: Vec<Rgb>,
+    text: Vec<Option<(char, Rgb)>>
+}
+
+impl TuiRenderer {
+    /// Fits a `width` by `height` frame, in board units, into the terminal.
+    pub fn new(columns: u32, rows: u32, width: f32, height: f32) -> Self {
+        let mut renderer = TuiRenderer {
+            columns: 0,
+            rows: 0,
+            scale: 1.,
+            width,
+            height,
+            background: [0, 0, 0],
+            pixels: Vec::new(),
+            text: Vec::new()
+        };
+        renderer.handle_frame_resize(columns, rows);
+        renderer
+    }
+
+    /// Top left cell of `(x, y)` in board units, if it's on screen.
+    fn cell(&self, board: &game::Board, x: f32, y: f32) -> Option<(u32, u32)> {
+        let pixel_height = 2 * self.rows;
+        let column = (x + (self.columns as f32 / self.scale - board.width) / 2.) * self.scale;
+        let pixel_y = (y + (pixel_height as f32 / self.scale - board.height) / 2.) * self.scale;
+        if column < 0. || column >= self.columns as f32 || pixel_y < 0. || pixel_y >= pixel_height as f32 {
+            return None;
+        }
+        Some((column as u32, self.rows - 1 - pixel_y as u32 / 2))
+    }
+
+    fn frame(&self) -> String {
+        // Drawn from the top left corner. Every cell is an upper half block
+        // with the top pixel as foreground and the bottom one as background,
+        // and colors are only sent when they change.
+        let mut out = String::from("\x1b[H");
+        let mut current = None;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let i = (row * self.columns + column) as usize;
+                let top = self.pixels[(2 * row * self.columns + column) as usize];
+                let bottom = self.pixels[((2 * row + 1) * self.columns + column) as usize];
+                let (c, foreground, background) = match self.text[i] {
+                    Some((c, color)) => (c, color, self.background),
+                    None => ('▀', top, bottom)
+                };
+                if current != Some((foreground, background)) {
+                    out.push_str(&format!("\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                                          foreground[0], foreground[1], foreground[2],
+                                          background[0], background[1], background[2]));
+                    current = Some((foreground, background));
+                }
+                out.push(c);
+            }
+            if row + 1 < self.rows {
+                out.push_str("\r\n");
+            }
+        }
+        out
+    }
+}
+
+impl Canvas for TuiRenderer {
+    fn draw_shape(&mut self, board: &game::Board, rect: game::Rect, shape: Shape, color: theme::Color) {
+        let color = to_rgb(color);
+        let pixel_height = 2 * self.rows;
+        let offset_x = (self.columns as f32 / self.scale - board.width) / 2.;
+        let offset_y = (pixel_height as f32 / self.scale - board.height) / 2.;
+        let to_pixel = |value: f32, offset: f32, limit: u32| {
+            (((value + offset) * self.scale - 0.5).ceil().max(0.) as u32).min(limit)
+        };
+        let (x0, mut x1) = (to_pixel(rect.x, offset_x, self.columns), to_pixel(rect.x + rect.width, offset_x, self.columns));
+        let (y0, mut y1) = (to_pixel(rect.y, offset_y, pixel_height), to_pixel(rect.y + rect.height, offset_y, pixel_height));
+        // Keep thin lines and the ball visible at coarse scales. Those are
+        // too small to show any rounding, so they're drawn as plain blocks.
+        let mut solid = shape == Shape::Rect;
+        if x1 == x0 && x0 < self.columns {
+            x1 = x0 + 1;
+            solid = true;
+        }
+        if y1 == y0 && y0 < pixel_height {
+            y1 = y0 + 1;
+            solid = true;
+        }
+        for y in y0..y1 {
+            let row = (pixel_height - 1 - y) * self.columns;
+            let board_y = (y as f32 + 0.5) / self.scale - offset_y;
+            for x in x0..x1 {
+                let board_x = (x as f32 + 0.5) / self.scale - offset_x;
+                if !solid && shape.distance(&rect, board_x, board_y) > 0. {
+                    continue;
+                }
+                self.pixels[(row + x) as usize] = color;
+            }
+        }
+    }
+
+    fn draw_text(&mut self, board: &game::Board, text: &str,
+                 pos: (f32, f32), size: font::Size, align: font::Align, color: theme::Color) {
+        let color = to_rgb(color);
+        let length = text.chars().count() as f32;
+        let column_width = 1. / self.scale;
+        let left = match align {
+            font::Align::Left => pos.0,
+            font::Align::Center => pos.0 - length * column_width / 2.,
+            font::Align::Right => pos.0 - length * column_width
+        };
+        if let Some((column, row)) = self.cell(board, left, pos.1 + size.height() / 2.) {
+            for (i, c) in text.to_uppercase().chars().enumerate() {
+                let column = column + i as u32;
+                if column < self.columns {
+                    self.text[(row * self.columns + column) as usize] = Some((c, color));
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for TuiRenderer {
+    fn handle_frame_resize(&mut self, columns: u32, rows: u32) {
+        self.columns = columns;
+        // Leave the last line free so printing it doesn't scroll.
+        self.rows = rows.saturating_sub(1).max(1);
+        self.scale = (columns as f32 / self.width).min(2. * self.rows as f32 / self.height);
+        self.pixels = vec![self.background; (2 * self.rows * self.columns) as usize];
+        self.text = vec![None; (self.rows * self.columns) as usize];
+    }
+
+    fn draw(&mut self, board: &game::Board, flow: &state::Match, effects: &effects::Effects) {
+        self.background = to_rgb(board.config.graphics.theme().background);
+        for pixel in self.pixels.iter_mut() {
+            *pixel = self.background;
+        }
+        for cell in self.text.iter_mut() {
+            *cell = None;
+        }
+        self.draw_scene(board, flow, effects, None);
+        print!("{}", self.frame());
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Plays a local match in the terminal. Returns the board as it was left,
+/// so the caller can save a recording that is still running.
+pub fn run(mut board: game::Board, mut flow: state::Match, mut player: Option<replay::ReplayPlayer>,
+           recording: &mut Option<(String, replay::Replay)>, two_player: bool) -> io::Result<game::Board> {
+    let (mut columns, mut rows) = terminal_size()?;
+    let padding = board.config.graphics.board_padding;
+    let mut renderer = TuiRenderer::new(columns, rows, board.width + 2. * padding, board.height + 2. * padding);
+    let _raw_mode = RawMode::enable()?;
+
+    let (keys_tx, keys_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut buf = [0u8; 64];
+        loop {
+            match stdin.lock().read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => if keys_tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut parser = KeyParser::default();
+    // Inputs considered held, with the ticks left until their release.
+    let mut held: Vec<(game::Input, u32)> = Vec::new();
+    let mut last_tick = Instant::now();
+    let mut accumulator = Duration::from_secs(0);
+    let tick_time = Duration::from_nanos(1000000000 / u64::from(game::TICK_RATE));
+    let mut frames = 0;
+    let mut effects = effects::Effects::new();
+    while !flow.should_quit() {
+        if board.winner().is_none() && player.as_ref().is_some_and(|player| player.is_finished(&board)) {
+            break;
+        }
+        let mut act = |flow: &mut state::Match, board: &mut game::Board, action: Action, is_pressed: bool| {
+            if let Some(input) = flow.handle_action(board, action, is_pressed) {
+                if let Some((_, ref mut replay)) = *recording {
+                    replay.record(board.tick(), input, is_pressed);
+                }
+            }
+        };
+        let mut keys = Vec::new();
+        while let Ok(bytes) = keys_rx.try_recv() {
+            keys.extend(parser.feed(&bytes));
+        }
+        keys.extend(parser.flush());
+        for key in keys {
+            if key == Key::Char('q') || key == Key::Char('\x03') {
+                return Ok(board);
+            }
+            let action = match key_action(key, two_player) {
+                Some(action) => action,
+                None => continue
+            };
+            if let Action::Board(input) = action {
+                if let Some(hold) = held.iter_mut().find(|&&mut (held_input, _)| held_input == input) {
+                    hold.1 = hold.1.max(KEY_REPEAT_TICKS);
+                    continue;
+                }
+                held.push((input, KEY_HOLD_TICKS));
+            }
+            act(&mut flow, &mut board, action, true);
+        }
+
+        accumulator += last_tick.elapsed();
+        last_tick = Instant::now();
+        while accumulator >= tick_time {
+            accumulator -= tick_time;
+            for hold in held.iter_mut() {
+                hold.1 -= 1;
+            }
+            for &(input, _) in held.iter().filter(|&&(_, ticks)| ticks == 0) {
+                act(&mut flow, &mut board, Action::Board(input), false);
+            }
+            held.retain(|&(_, ticks)| ticks > 0);
+            if flow.tick(&board) {
+                if let Some(ref mut player) = player {
+                    player.apply(&mut board);
+                }
+                board.update(game::TICK_DT);
+                for event in board.drain_events() {
+                    effects.handle_event(&board, &event);
+                    flow.handle_event(&event);
+                }
+            }
+            effects.update(&board, game::TICK_DT);
+        }
+        if board.winner().is_some() {
+            replay::save_recording(recording, &board);
+        }
+
+        frames += 1;
+        if frames % RESIZE_CHECK_FRAMES == 0 {
+            match terminal_size() {
+                Ok(size) if size != (columns, rows) => {
+                    columns = size.0;
+                    rows = size.1;
+                    print!("\x1b[2J");
+                    renderer.handle_frame_resize(columns, rows);
+                },
+                _ => ()
+            }
+        }
+        renderer.draw(&board, &flow, &effects);
+        thread::sleep(FRAME_TIME);
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_arrows_and_plain_keys() {
+        let mut parser = KeyParser::default();
+        assert_eq!(parser.feed(b"\x1b[A\x1b[Bw\r"), vec![Key::Up, Key::Down, Key::Char('w'), Key::Enter]);
+        // Other sequences, like the right arrow, are skipped.
+        assert_eq!(parser.feed(b"\x1b[Cp"), vec![Key::Char('p')]);
+        assert!(parser.flush().is_empty());
+    }
+
+    #[test]
+    fn a_lone_escape_is_the_escape_key() {
+        let mut parser = KeyParser::default();
+        assert_eq!(parser.feed(b"\x1bp"), vec![Key::Escape, Key::Char('p')]);
+        assert!(parser.feed(b"\x1b").is_empty());
+        assert_eq!(parser.flush(), vec![Key::Escape]);
+        assert!(parser.flush().is_empty());
+    }
+
+    #[test]
+    fn joins_a_sequence_split_across_reads() {
+        let mut parser = KeyParser::default();
+        assert!(parser.feed(b"\x1b").is_empty());
+        assert_eq!(parser.feed(b"[A"), vec![Key::Up]);
+        assert_eq!(parser.feed(b"s\x1b["), vec![Key::Char('s')]);
+        assert!(parser.flush().is_empty());
+        assert_eq!(parser.feed(b"B"), vec![Key::Down]);
+    }
+
+    #[test]
+    fn arrows_move_the_right_paddle_in_two_player_mode() {
+        assert_eq!(key_action(Key::Up, false), Some(Action::Board(game::Input::Up(game::Side::Lhs))));
+        assert_eq!(key_action(Key::Char('w'), false), None);
+        assert_eq!(key_action(Key::Up, true), Some(Action::Board(game::Input::Up(game::Side::Rhs))));
+        assert_eq!(key_action(Key::Char('w'), true), Some(Action::Board(game::Input::Up(game::Side::Lhs))));
+        assert_eq!(key_action(Key::Char('s'), true), Some(Action::Board(game::Input::Down(game::Side::Lhs))));
+        assert_eq!(key_action(Key::Escape, true), Some(Action::Pause));
+        assert_eq!(key_action(Key::Enter, false), Some(Action::Confirm));
+    }
+}